@@ -0,0 +1,19 @@
+#!/usr/bin/env bash
+# Builds the watcher in the developer mode, runs it with the given arguments
+# until Ctrl+C and turns the recorded spans into flamegraph.svg
+#
+#   $ ./scripts/flamegraph.sh --accounts aurora mainnet
+#
+# Needs inferno: cargo install inferno
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+cargo build --release --features developer-mode
+# Ctrl+C stops the watcher, not the script
+trap '' INT
+./target/release/near-lake-accounts-watcher --developer-mode "$@" || true
+trap - INT
+
+inferno-flamegraph < tracing.folded > flamegraph.svg
+echo "Written flamegraph.svg"
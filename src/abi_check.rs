@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{ActionView, ReceiptEnumView};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+/// The function call arguments don't match the ABI (used by `--abi-check`)
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct AbiValidationFailure {
+    pub receipt_id: String,
+    pub receiver_id: AccountId,
+    pub method_name: String,
+    pub error: String,
+    pub block_height: BlockHeight,
+}
+
+/// Why the arguments are rejected
+#[derive(Debug)]
+pub(crate) enum AbiError {
+    UnknownMethod,
+    InvalidJson(String),
+    InvalidArgument { name: String, reason: String },
+}
+
+impl std::fmt::Display for AbiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbiError::UnknownMethod => write!(f, "the method is not in the ABI"),
+            AbiError::InvalidJson(err) => write!(f, "the arguments are not a JSON object: {}", err),
+            AbiError::InvalidArgument { name, reason } => {
+                write!(f, "argument `{}` {}", name, reason)
+            }
+        }
+    }
+}
+
+/// Parameter of a function from the ABI
+struct AbiParam {
+    name: String,
+    type_schema: serde_json::Value,
+}
+
+/// Validates the JSON arguments of the function calls against the NEAR ABI
+/// generated by `cargo-near`. Only the subset of JSON Schema that `schemars`
+/// produces for the contract types is supported: `type`, `enum`, `$ref`,
+/// `properties`/`required`, `items`, `anyOf`/`oneOf`/`allOf`
+pub(crate) struct NearAbiValidator {
+    /// `None` for the functions with borsh arguments, we can't check those
+    functions: HashMap<String, Option<Vec<AbiParam>>>,
+    definitions: serde_json::Map<String, serde_json::Value>,
+}
+
+impl NearAbiValidator {
+    pub(crate) fn load(abi_file: &std::path::Path) -> anyhow::Result<Self> {
+        let content = std::fs::read(abi_file)
+            .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", abi_file.display(), err))?;
+        let abi: serde_json::Value = serde_json::from_slice(&content)
+            .map_err(|err| anyhow::anyhow!("Invalid ABI {}: {}", abi_file.display(), err))?;
+        let functions = abi["body"]["functions"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("ABI {} has no functions", abi_file.display()))?
+            .iter()
+            .filter_map(|function| {
+                let name = function["name"].as_str()?.to_string();
+                let params = &function["params"];
+                let args = match params["serialization_type"].as_str() {
+                    Some("borsh") => None,
+                    // A function without parameters has no `params` at all
+                    _ => Some(
+                        params["args"]
+                            .as_array()
+                            .map(|args| {
+                                args.iter()
+                                    .filter_map(|arg| {
+                                        Some(AbiParam {
+                                            name: arg["name"].as_str()?.to_string(),
+                                            type_schema: arg["type_schema"].clone(),
+                                        })
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    ),
+                };
+                Some((name, args))
+            })
+            .collect();
+        let definitions = abi["body"]["root_schema"]["definitions"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        Ok(Self {
+            functions,
+            definitions,
+        })
+    }
+
+    pub(crate) fn validate(&self, method: &str, args: &[u8]) -> Result<(), AbiError> {
+        let params = match self.functions.get(method) {
+            Some(Some(params)) => params,
+            Some(None) => return Ok(()),
+            None => return Err(AbiError::UnknownMethod),
+        };
+        let args: serde_json::Value = if args.is_empty() && params.is_empty() {
+            serde_json::Value::Object(serde_json::Map::new())
+        } else {
+            serde_json::from_slice(args).map_err(|err| AbiError::InvalidJson(err.to_string()))?
+        };
+        let args = args
+            .as_object()
+            .ok_or_else(|| AbiError::InvalidJson("not an object".to_string()))?;
+        for param in params {
+            // A missing optional argument is the same as `null` for near-sdk
+            let value = args.get(&param.name).unwrap_or(&serde_json::Value::Null);
+            self.check(&param.type_schema, value)
+                .map_err(|reason| AbiError::InvalidArgument {
+                    name: param.name.clone(),
+                    reason,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Checks the value against the schema and describes the mismatch
+    fn check(&self, schema: &serde_json::Value, value: &serde_json::Value) -> Result<(), String> {
+        // `true` and `{}` accept anything
+        let schema = match schema {
+            serde_json::Value::Object(schema) => schema,
+            _ => return Ok(()),
+        };
+        if let Some(reference) = schema.get("$ref").and_then(|reference| reference.as_str()) {
+            let name = reference.trim_start_matches("#/definitions/");
+            let definition = self
+                .definitions
+                .get(name)
+                .ok_or_else(|| format!("refers to unknown definition {}", reference))?;
+            return self.check(definition, value);
+        }
+        if let Some(schemas) = schema.get("allOf").and_then(|schemas| schemas.as_array()) {
+            for schema in schemas {
+                self.check(schema, value)?;
+            }
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(schemas) = schema.get(key).and_then(|schemas| schemas.as_array()) {
+                if !schemas
+                    .iter()
+                    .any(|schema| self.check(schema, value).is_ok())
+                {
+                    return Err(format!("matches none of the variants: {}", value));
+                }
+            }
+        }
+        if let Some(variants) = schema.get("enum").and_then(|variants| variants.as_array()) {
+            if !variants.contains(value) {
+                return Err(format!("is {}, expected one of {:?}", value, variants));
+            }
+        }
+        if let Some(expected) = schema.get("type") {
+            let is_expected = match expected {
+                serde_json::Value::String(expected) => is_type(expected, value),
+                serde_json::Value::Array(expected) => expected
+                    .iter()
+                    .filter_map(|expected| expected.as_str())
+                    .any(|expected| is_type(expected, value)),
+                _ => true,
+            };
+            if !is_expected {
+                return Err(format!("is {}, expected {}", value, expected));
+            }
+        }
+        if let Some(object) = value.as_object() {
+            if let Some(required) = schema
+                .get("required")
+                .and_then(|required| required.as_array())
+            {
+                for field in required.iter().filter_map(|field| field.as_str()) {
+                    if !object.contains_key(field) {
+                        return Err(format!("misses the required field `{}`", field));
+                    }
+                }
+            }
+            if let Some(properties) = schema
+                .get("properties")
+                .and_then(|properties| properties.as_object())
+            {
+                for (field, field_schema) in properties {
+                    if let Some(field_value) = object.get(field) {
+                        self.check(field_schema, field_value)
+                            .map_err(|reason| format!(".{} {}", field, reason))?;
+                    }
+                }
+            }
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (index, item) in array.iter().enumerate() {
+                self.check(items, item)
+                    .map_err(|reason| format!("[{}] {}", index, reason))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the function calls of the receipt, `receiver_id` is expected
+    /// to be the contract of the ABI
+    pub(crate) fn validation_failures(
+        &self,
+        outcome: &IndexerExecutionOutcomeWithReceipt,
+        block_height: BlockHeight,
+    ) -> Vec<AbiValidationFailure> {
+        let actions = match &outcome.receipt.receipt {
+            ReceiptEnumView::Action { actions, .. } => actions,
+            ReceiptEnumView::Data { .. } => return vec![],
+        };
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                ActionView::FunctionCall {
+                    method_name, args, ..
+                } => {
+                    let args = base64::decode(args).unwrap_or_default();
+                    let err = self.validate(method_name, &args).err()?;
+                    Some(AbiValidationFailure {
+                        receipt_id: outcome.receipt.receipt_id.to_string(),
+                        receiver_id: outcome.receipt.receiver_id.clone(),
+                        method_name: method_name.clone(),
+                        error: err.to_string(),
+                        block_height,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn is_type(expected: &str, value: &serde_json::Value) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
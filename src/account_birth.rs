@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// Remembers the accounts we have already seen an `AccountUpdate` of
+/// (used by `--account-birth-tracking`)
+pub(crate) struct AccountBirthTracker {
+    seen: HashSet<AccountId>,
+    cache_file: Option<PathBuf>,
+}
+
+impl AccountBirthTracker {
+    /// Loads the accounts seen by the previous runs from the `--account-birth-cache-file`
+    pub(crate) fn load(cache_file: Option<PathBuf>) -> anyhow::Result<Self> {
+        let seen = match &cache_file {
+            Some(cache_file) if cache_file.exists() => {
+                let content = std::fs::read(cache_file).map_err(|err| {
+                    anyhow::anyhow!("Failed to read {}: {}", cache_file.display(), err)
+                })?;
+                serde_json::from_slice(&content).map_err(|err| {
+                    anyhow::anyhow!(
+                        "Invalid account birth cache {}: {}",
+                        cache_file.display(),
+                        err
+                    )
+                })?
+            }
+            _ => HashSet::new(),
+        };
+        Ok(Self { seen, cache_file })
+    }
+
+    /// Returns true the first time the account is seen
+    pub(crate) fn is_first_seen(&mut self, account_id: &AccountId) -> bool {
+        if self.seen.contains(account_id) {
+            return false;
+        }
+        self.seen.insert(account_id.clone())
+    }
+
+    /// Writes the seen accounts to the `--account-birth-cache-file` if it's set
+    pub(crate) fn save(&self) -> anyhow::Result<()> {
+        if let Some(cache_file) = &self.cache_file {
+            let mut accounts: Vec<&AccountId> = self.seen.iter().collect();
+            accounts.sort();
+            std::fs::write(cache_file, serde_json::to_vec(&accounts)?).map_err(|err| {
+                anyhow::anyhow!("Failed to write {}: {}", cache_file.display(), err)
+            })?;
+        }
+        Ok(())
+    }
+}
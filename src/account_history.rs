@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// The history file of a single account
+struct AccountHistoryFile {
+    writer: BufWriter<File>,
+    /// The events of the block being written, compressed into one gzip member on
+    /// the flush, so the file stays a valid gzip file if the watcher is killed
+    gzip: Option<GzEncoder<Vec<u8>>>,
+    /// The size of the uncompressed events
+    size: u64,
+    /// The height of the last event in the file, the events up to this height
+    /// were written by the previous run and are skipped
+    last_block_height: Option<BlockHeight>,
+}
+
+impl AccountHistoryFile {
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        match &mut self.gzip {
+            Some(gzip) => gzip.write_all(line.as_bytes())?,
+            None => self.writer.write_all(line.as_bytes())?,
+        }
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self, gzip_level: Option<Compression>) -> std::io::Result<()> {
+        if let (Some(gzip), Some(gzip_level)) = (&mut self.gzip, gzip_level) {
+            if !gzip.get_ref().is_empty() {
+                let member =
+                    std::mem::replace(gzip, GzEncoder::new(Vec::new(), gzip_level)).finish()?;
+                self.writer.write_all(&member)?;
+            }
+        }
+        self.writer.flush()
+    }
+}
+
+/// Writes the matched events of each account to its own `<account_id>.ndjson`
+/// file, or `<account_id>.ndjson.gz` with the gzip compression
+pub(crate) struct AccountHistoryWriter {
+    dir: PathBuf,
+    max_size_bytes: Option<u64>,
+    gzip_level: Option<Compression>,
+    files: HashMap<AccountId, AccountHistoryFile>,
+}
+
+impl AccountHistoryWriter {
+    pub(crate) fn new(
+        dir: PathBuf,
+        max_size_mb: Option<u64>,
+        gzip_level: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| anyhow::anyhow!("Failed to create {}: {}", dir.display(), err))?;
+        Ok(Self {
+            dir,
+            max_size_bytes: max_size_mb.map(|max_size_mb| max_size_mb * 1024 * 1024),
+            gzip_level: gzip_level.map(Compression::new),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Appends the event to the account's history, the file is opened on the first event
+    pub(crate) fn write(
+        &mut self,
+        account_id: &AccountId,
+        block_height: BlockHeight,
+        event: &serde_json::Value,
+    ) -> std::io::Result<()> {
+        if !self.files.contains_key(account_id) {
+            let file = self.open(account_id)?;
+            self.files.insert(account_id.clone(), file);
+        }
+        let path = self.path(account_id);
+        let extension = self.extension();
+        let max_size_bytes = self.max_size_bytes;
+        let gzip_level = self.gzip_level;
+        let file = self
+            .files
+            .get_mut(account_id)
+            .expect("History file has just been opened");
+
+        if file
+            .last_block_height
+            .map_or(false, |last_block_height| block_height <= last_block_height)
+        {
+            return Ok(());
+        }
+
+        let line = format!("{}\n", event);
+        if let Some(max_size_bytes) = max_size_bytes {
+            if file.size > 0 && file.size + line.len() as u64 > max_size_bytes {
+                file.flush(gzip_level)?;
+                rotate(&path, extension, block_height)?;
+                file.writer = BufWriter::new(open_append(&path)?);
+                file.size = 0;
+            }
+        }
+        file.write(&line)
+    }
+
+    pub(crate) fn flush(&mut self) -> std::io::Result<()> {
+        for file in self.files.values_mut() {
+            file.flush(self.gzip_level)?;
+        }
+        Ok(())
+    }
+
+    fn extension(&self) -> &'static str {
+        if self.gzip_level.is_some() {
+            "ndjson.gz"
+        } else {
+            "ndjson"
+        }
+    }
+
+    fn path(&self, account_id: &AccountId) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", account_id, self.extension()))
+    }
+
+    fn open(&self, account_id: &AccountId) -> std::io::Result<AccountHistoryFile> {
+        let path = self.path(account_id);
+        let (last_block_height, size) = read_history(&path, self.gzip_level.is_some())?;
+        let file = open_append(&path)?;
+        Ok(AccountHistoryFile {
+            writer: BufWriter::new(file),
+            gzip: self
+                .gzip_level
+                .map(|level| GzEncoder::new(Vec::new(), level)),
+            size,
+            last_block_height,
+        })
+    }
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// Reads the block height of the last event in the existing history file
+/// and the size of its events. A gzip member cut off by a kill is skipped
+fn read_history(path: &Path, gzip: bool) -> std::io::Result<(Option<BlockHeight>, u64)> {
+    if !path.exists() {
+        return Ok((None, 0));
+    }
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if gzip {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut size = 0;
+    let mut last_line = None;
+    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+        size += line.len() as u64 + 1;
+        if !line.trim().is_empty() {
+            last_line = Some(line);
+        }
+    }
+    let last_block_height = last_line
+        .and_then(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+        .and_then(|event| event["block_height"].as_u64());
+    Ok((last_block_height, size))
+}
+
+/// Moves the full history file aside as `<account_id>.<block_height>.ndjson`,
+/// the later rotations in the same block get `<account_id>.<block_height>.<n>.ndjson`
+fn rotate(path: &Path, extension: &str, block_height: BlockHeight) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| file_name.strip_suffix(extension))
+        .expect("History file is named after the account");
+    let mut rotated_path =
+        path.with_file_name(format!("{}{}.{}", file_name, block_height, extension));
+    let mut sequence = 0;
+    while rotated_path.exists() {
+        sequence += 1;
+        rotated_path = path.with_file_name(format!(
+            "{}{}.{}.{}",
+            file_name, block_height, sequence, extension
+        ));
+    }
+    std::fs::rename(path, rotated_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clean directory for each test
+    fn history_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("near-lake-accounts-watcher-account-history-test")
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn event(block_height: BlockHeight) -> serde_json::Value {
+        serde_json::json!({ "block_height": block_height })
+    }
+
+    fn read_gzip(path: &Path) -> String {
+        let mut content = String::new();
+        MultiGzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn rotations_in_one_block_keep_every_file() {
+        let dir = history_dir("rotation");
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut writer = AccountHistoryWriter::new(dir.clone(), Some(1), None).unwrap();
+        writer.max_size_bytes = Some(30);
+        writer.write(&account_id, 1, &event(1)).unwrap();
+        for _ in 0..3 {
+            writer.write(&account_id, 2, &event(2)).unwrap();
+        }
+        writer.flush().unwrap();
+        let read = |file_name: &str| std::fs::read_to_string(dir.join(file_name)).unwrap();
+        assert_eq!(read("alice.near.2.ndjson"), "{\"block_height\":1}\n");
+        assert_eq!(read("alice.near.2.1.ndjson"), "{\"block_height\":2}\n");
+        assert_eq!(read("alice.near.2.2.ndjson"), "{\"block_height\":2}\n");
+        assert_eq!(read("alice.near.ndjson"), "{\"block_height\":2}\n");
+    }
+
+    #[test]
+    fn gzip_file_is_valid_after_each_block() {
+        let dir = history_dir("gzip");
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut writer = AccountHistoryWriter::new(dir.clone(), None, Some(6)).unwrap();
+        writer.write(&account_id, 1, &event(1)).unwrap();
+        writer.write(&account_id, 1, &event(1)).unwrap();
+        writer.flush().unwrap();
+        let path = dir.join("alice.near.ndjson.gz");
+        assert_eq!(read_gzip(&path), "{\"block_height\":1}\n".repeat(2));
+        writer.write(&account_id, 2, &event(2)).unwrap();
+        writer.flush().unwrap();
+        // Nothing written since the last flush adds no member
+        writer.flush().unwrap();
+        drop(writer);
+
+        // The restarted watcher skips the blocks already in the file
+        let mut writer = AccountHistoryWriter::new(dir.clone(), None, Some(6)).unwrap();
+        writer.write(&account_id, 2, &event(2)).unwrap();
+        writer.write(&account_id, 3, &event(3)).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            read_gzip(&path),
+            "{\"block_height\":1}\n{\"block_height\":1}\n{\"block_height\":2}\n{\"block_height\":3}\n"
+        );
+    }
+
+    #[test]
+    fn gzip_file_is_rotated_with_its_extension() {
+        let dir = history_dir("gzip-rotation");
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut writer = AccountHistoryWriter::new(dir.clone(), Some(1), Some(1)).unwrap();
+        writer.max_size_bytes = Some(30);
+        writer.write(&account_id, 1, &event(1)).unwrap();
+        writer.write(&account_id, 2, &event(2)).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            read_gzip(&dir.join("alice.near.2.ndjson.gz")),
+            "{\"block_height\":1}\n"
+        );
+        assert_eq!(
+            read_gzip(&dir.join("alice.near.ndjson.gz")),
+            "{\"block_height\":2}\n"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// We forget the alerts whose window is over once there are this many,
+/// so the watchers with a lot of alerting accounts don't grow forever
+const PRUNE_THRESHOLD: usize = 10_000;
+/// The silenced alerts are reported on the first one and then every N-th one
+const SILENCED_ALERTS_LOG_INTERVAL: u64 = 100;
+
+/// Suppresses the repeated alerts of the same account and change type
+/// (used by `--alert-silence-window`). The window starts at the last alert
+/// that went out, the silenced ones don't extend it
+pub(crate) struct AlertSilencer {
+    window: Duration,
+    last_alerted: HashMap<(AccountId, String), Instant>,
+    alerts_silenced_total: u64,
+}
+
+impl AlertSilencer {
+    pub(crate) fn new(window_secs: u64) -> Self {
+        Self {
+            window: Duration::from_secs(window_secs),
+            last_alerted: HashMap::new(),
+            alerts_silenced_total: 0,
+        }
+    }
+
+    /// Returns whether the alert has to be suppressed, otherwise remembers it went out
+    pub(crate) fn is_silenced(&mut self, account_id: &AccountId, alert_type: &str) -> bool {
+        self.is_silenced_at(account_id, alert_type, Instant::now())
+    }
+
+    fn is_silenced_at(&mut self, account_id: &AccountId, alert_type: &str, now: Instant) -> bool {
+        let key = (account_id.clone(), alert_type.to_string());
+        match self.last_alerted.get(&key) {
+            Some(alerted_at) if now.duration_since(*alerted_at) < self.window => {
+                self.alerts_silenced_total += 1;
+                if self.alerts_silenced_total % SILENCED_ALERTS_LOG_INTERVAL == 1 {
+                    eprintln!(
+                        "Silenced the {} alert of {} (alerts_silenced_total: {})",
+                        alert_type, account_id, self.alerts_silenced_total
+                    );
+                }
+                true
+            }
+            _ => {
+                if self.last_alerted.len() >= PRUNE_THRESHOLD {
+                    let window = self.window;
+                    self.last_alerted
+                        .retain(|_, alerted_at| now.duration_since(*alerted_at) < window);
+                }
+                self.last_alerted.insert(key, now);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silences_repeated_alerts_within_window() {
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut silencer = AlertSilencer::new(60);
+        let start = Instant::now();
+        assert!(!silencer.is_silenced_at(&account_id, "access_key_update", start));
+        assert!(silencer.is_silenced_at(
+            &account_id,
+            "access_key_update",
+            start + Duration::from_secs(30)
+        ));
+        // The silenced alert doesn't extend the window
+        assert!(!silencer.is_silenced_at(
+            &account_id,
+            "access_key_update",
+            start + Duration::from_secs(60)
+        ));
+        assert_eq!(silencer.alerts_silenced_total, 1);
+    }
+
+    #[test]
+    fn keeps_account_and_change_type_pairs_apart() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let mut silencer = AlertSilencer::new(60);
+        let now = Instant::now();
+        assert!(!silencer.is_silenced_at(&alice, "access_key_update", now));
+        assert!(!silencer.is_silenced_at(&alice, "contract_code_update", now));
+        assert!(!silencer.is_silenced_at(&bob, "access_key_update", now));
+        assert_eq!(silencer.alerts_silenced_total, 0);
+    }
+}
@@ -0,0 +1,253 @@
+//! Decoding of the proposals of the AstroDAO (Sputnik DAO v2) contracts
+//! ref https://github.com/near-daos/sputnik-dao-contract/tree/main/sputnikdao2
+
+use std::collections::{HashMap, HashSet};
+
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// The DAOs created by `sputnik-dao.near` factory
+const DAO_ACCOUNT_SUFFIX: &str = ".sputnik-dao.near";
+
+/// `StorageKeys::Proposals` of the contract, the proposal key is the prefix
+/// followed by the proposal id (u64 LE)
+const PROPOSALS_PREFIX: u8 = 3;
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct DaoEvent {
+    #[serde(skip)]
+    pub event_type: &'static str,
+    pub proposal_id: u64,
+    pub proposer: String,
+    pub status: String,
+    /// Approve, reject and remove weights by role
+    pub vote_counts: HashMap<String, [String; 3]>,
+}
+
+pub(crate) struct AstroDaoDecoder;
+
+impl AstroDaoDecoder {
+    pub(crate) fn is_dao_account(account_id: &AccountId) -> bool {
+        account_id.as_ref().ends_with(DAO_ACCOUNT_SUFFIX)
+    }
+
+    /// Recognizes the proposal storage entry. The contract keeps no history, so we
+    /// tell the events apart by the proposal itself: a proposal in progress with no
+    /// votes has just been added, with votes it has been voted on, and any other
+    /// status means it has been finalized
+    pub(crate) fn decode(key: &[u8], value: &[u8]) -> Option<DaoEvent> {
+        let proposal_id = match key {
+            [PROPOSALS_PREFIX, proposal_id @ ..] if proposal_id.len() == 8 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(proposal_id);
+                u64::from_le_bytes(bytes)
+            }
+            _ => return None,
+        };
+        let VersionedProposal::Default(proposal) = VersionedProposal::try_from_slice(value).ok()?;
+
+        let event_type = match (&proposal.status, proposal.votes.is_empty()) {
+            (ProposalStatus::InProgress, true) => "dao_proposal_added",
+            (ProposalStatus::InProgress, false) => "dao_proposal_voted",
+            _ => "dao_proposal_finalized",
+        };
+        Some(DaoEvent {
+            event_type,
+            proposal_id,
+            proposer: proposal.proposer,
+            status: format!("{:?}", proposal.status),
+            vote_counts: proposal
+                .vote_counts
+                .into_iter()
+                .map(|(role, counts)| (role, counts.map(|count| count.to_string())))
+                .collect(),
+        })
+    }
+}
+
+#[derive(BorshDeserialize)]
+enum VersionedProposal {
+    Default(Proposal),
+}
+
+#[derive(BorshDeserialize)]
+struct Proposal {
+    proposer: String,
+    _description: String,
+    _kind: ProposalKind,
+    status: ProposalStatus,
+    vote_counts: HashMap<String, [u128; 3]>,
+    votes: HashMap<String, Vote>,
+    _submission_time: u64,
+}
+
+#[derive(Debug, BorshDeserialize)]
+enum ProposalStatus {
+    InProgress,
+    Approved,
+    Rejected,
+    Removed,
+    Expired,
+    Moved,
+    Failed,
+}
+
+#[derive(BorshDeserialize)]
+enum Vote {
+    Approve,
+    Reject,
+    Remove,
+}
+
+// The kind sits between the description and the status, so it has to be read in full
+// even though we don't print it. The fields mirror the contract types as stored by borsh
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum ProposalKind {
+    ChangeConfig {
+        config: Config,
+    },
+    ChangePolicy {
+        policy: VersionedPolicy,
+    },
+    AddMemberToRole {
+        member_id: String,
+        role: String,
+    },
+    RemoveMemberFromRole {
+        member_id: String,
+        role: String,
+    },
+    FunctionCall {
+        receiver_id: String,
+        actions: Vec<ActionCall>,
+    },
+    UpgradeSelf {
+        hash: [u8; 32],
+    },
+    UpgradeRemote {
+        receiver_id: String,
+        method_name: String,
+        hash: [u8; 32],
+    },
+    Transfer {
+        token_id: String,
+        receiver_id: String,
+        amount: u128,
+        msg: Option<String>,
+    },
+    SetStakingContract {
+        staking_id: String,
+    },
+    AddBounty {
+        bounty: Bounty,
+    },
+    BountyDone {
+        bounty_id: u64,
+        receiver_id: String,
+    },
+    Vote,
+    FactoryInfoUpdate {
+        factory_id: String,
+        auto_update: bool,
+    },
+    ChangePolicyAddOrUpdateRole {
+        role: RolePermission,
+    },
+    ChangePolicyRemoveRole {
+        role: String,
+    },
+    ChangePolicyUpdateDefaultVotePolicy {
+        vote_policy: VotePolicy,
+    },
+    ChangePolicyUpdateParameters {
+        proposal_bond: Option<u128>,
+        proposal_period: Option<u64>,
+        bounty_bond: Option<u128>,
+        bounty_forgiveness_period: Option<u64>,
+    },
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct Config {
+    name: String,
+    purpose: String,
+    metadata: Vec<u8>,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum VersionedPolicy {
+    Default(Vec<String>),
+    Current(Policy),
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct Policy {
+    roles: Vec<RolePermission>,
+    default_vote_policy: VotePolicy,
+    proposal_bond: u128,
+    proposal_period: u64,
+    bounty_bond: u128,
+    bounty_forgiveness_period: u64,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct RolePermission {
+    name: String,
+    kind: RoleKind,
+    permissions: HashSet<String>,
+    vote_policy: HashMap<String, VotePolicy>,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum RoleKind {
+    Everyone,
+    Member(u128),
+    Group(HashSet<String>),
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct VotePolicy {
+    weight_kind: WeightKind,
+    quorum: u128,
+    threshold: WeightOrRatio,
+}
+
+#[derive(BorshDeserialize)]
+enum WeightKind {
+    TokenWeight,
+    RoleWeight,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum WeightOrRatio {
+    Weight(u128),
+    Ratio(u64, u64),
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct ActionCall {
+    method_name: String,
+    args: Vec<u8>,
+    deposit: u128,
+    gas: u64,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct Bounty {
+    description: String,
+    token: String,
+    amount: u128,
+    times: u32,
+    max_deadline: u64,
+}
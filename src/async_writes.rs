@@ -0,0 +1,228 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight, ShardId};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+use crate::account_history::AccountHistoryWriter;
+use crate::configs::AsyncWriteErrorMode;
+use crate::duckdb_sink::DuckDbSink;
+
+/// With `--async-write-error-mode drop` we report the first dropped write
+/// and then every N-th one, so we don't flood stderr
+const DROPPED_WRITES_LOG_INTERVAL: u64 = 1000;
+
+/// A matched change on its way to one of the sinks
+pub(crate) enum SinkWrite {
+    AccountHistory {
+        account_id: AccountId,
+        block_height: BlockHeight,
+        event: serde_json::Value,
+    },
+    DuckDb {
+        block_height: BlockHeight,
+        block_hash: CryptoHash,
+        shard_id: ShardId,
+        account_id: AccountId,
+        change: serde_json::Value,
+    },
+    /// Writes out what the sinks have buffered (used by `--max-memory-mb`)
+    Flush,
+}
+
+/// The places the matched changes are written to besides stdout
+#[derive(Default)]
+pub(crate) struct Sinks {
+    /// Per-account history files (used by `--near-account-history`)
+    pub account_history: Option<AccountHistoryWriter>,
+    /// The database with the matched changes (used by `--duckdb-path`)
+    pub duckdb: Option<DuckDbSink>,
+}
+
+impl Sinks {
+    fn write(&mut self, write: SinkWrite) {
+        match write {
+            SinkWrite::AccountHistory {
+                account_id,
+                block_height,
+                event,
+            } => {
+                if let Some(account_history) = &mut self.account_history {
+                    if let Err(err) = account_history.write(&account_id, block_height, &event) {
+                        eprintln!("Failed to write the account history: {}", err);
+                    }
+                }
+            }
+            SinkWrite::DuckDb {
+                block_height,
+                block_hash,
+                shard_id,
+                account_id,
+                change,
+            } => {
+                if let Some(duckdb) = &self.duckdb {
+                    if let Err(err) = duckdb.insert(
+                        block_height,
+                        &block_hash,
+                        shard_id,
+                        account_id.as_ref(),
+                        &change,
+                    ) {
+                        eprintln!("Failed to insert the change into DuckDB: {}", err);
+                    }
+                }
+            }
+            SinkWrite::Flush => {
+                if let Some(account_history) = &mut self.account_history {
+                    if let Err(err) = account_history.flush() {
+                        eprintln!("Failed to flush the account history: {}", err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum Mode {
+    /// The changes are written right away while the block is handled
+    Sync(Sinks),
+    /// The changes are queued to the task that owns the sinks (used by `--async-writes`)
+    Async {
+        sender: tokio::sync::mpsc::Sender<SinkWrite>,
+        writer_task: tokio::task::JoinHandle<Sinks>,
+        queue_capacity: usize,
+        error_mode: AsyncWriteErrorMode,
+        dropped_writes: u64,
+    },
+}
+
+/// Writes the matched changes to the sinks either in place or, with `--async-writes`,
+/// in the background, so a slow disk or database doesn't hold the stream back
+pub(crate) struct SinkWriter {
+    mode: Mode,
+    has_sinks: bool,
+}
+
+impl Default for SinkWriter {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Sync(Sinks::default()),
+            has_sinks: false,
+        }
+    }
+}
+
+impl SinkWriter {
+    pub(crate) fn new(sinks: Sinks) -> Self {
+        Self {
+            has_sinks: sinks.account_history.is_some() || sinks.duckdb.is_some(),
+            mode: Mode::Sync(sinks),
+        }
+    }
+
+    /// Moves the sinks to a blocking task, they do file and database I/O
+    pub(crate) fn new_async(
+        sinks: Sinks,
+        queue_capacity: usize,
+        error_mode: AsyncWriteErrorMode,
+    ) -> Self {
+        let has_sinks = sinks.account_history.is_some() || sinks.duckdb.is_some();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(queue_capacity);
+        let writer_task = tokio::task::spawn_blocking(move || {
+            let mut sinks = sinks;
+            while let Some(write) = receiver.blocking_recv() {
+                sinks.write(write);
+            }
+            sinks
+        });
+        Self {
+            mode: Mode::Async {
+                sender,
+                writer_task,
+                queue_capacity,
+                error_mode,
+                dropped_writes: 0,
+            },
+            has_sinks,
+        }
+    }
+
+    pub(crate) fn has_sinks(&self) -> bool {
+        self.has_sinks
+    }
+
+    /// Number of the writes waiting in the queue, always 0 without `--async-writes`
+    pub(crate) fn queue_depth(&self) -> usize {
+        match &self.mode {
+            Mode::Sync(_) => 0,
+            Mode::Async {
+                sender,
+                queue_capacity,
+                ..
+            } => queue_capacity - sender.capacity(),
+        }
+    }
+
+    /// Fails if the writer task has stopped or the queue is full
+    /// with `--async-write-error-mode abort`
+    pub(crate) async fn write(&mut self, write: SinkWrite) -> anyhow::Result<()> {
+        match &mut self.mode {
+            Mode::Sync(sinks) => sinks.write(write),
+            Mode::Async {
+                sender,
+                error_mode,
+                dropped_writes,
+                queue_capacity,
+                ..
+            } => {
+                let write = match sender.try_send(write) {
+                    Ok(()) => return Ok(()),
+                    Err(tokio::sync::mpsc::error::TrySendError::Full(write)) => write,
+                    Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
+                        anyhow::bail!("The async writer task has stopped")
+                    }
+                };
+                match error_mode {
+                    AsyncWriteErrorMode::Drop => {
+                        *dropped_writes += 1;
+                        if *dropped_writes % DROPPED_WRITES_LOG_INTERVAL == 1 {
+                            eprintln!(
+                                "The async write queue is full, dropped {} writes so far",
+                                dropped_writes
+                            );
+                        }
+                    }
+                    AsyncWriteErrorMode::Block => {
+                        if sender.send(write).await.is_err() {
+                            anyhow::bail!("The async writer task has stopped");
+                        }
+                    }
+                    AsyncWriteErrorMode::Abort => anyhow::bail!(
+                        "The async write queue is full ({} writes), stopping",
+                        queue_capacity
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for the queued writes and returns the sinks, so they can be closed
+    pub(crate) async fn finish(self) -> anyhow::Result<Sinks> {
+        match self.mode {
+            Mode::Sync(sinks) => Ok(sinks),
+            Mode::Async {
+                sender,
+                writer_task,
+                dropped_writes,
+                ..
+            } => {
+                drop(sender);
+                if dropped_writes > 0 {
+                    eprintln!(
+                        "Dropped {} writes, the async write queue was full",
+                        dropped_writes
+                    );
+                }
+                Ok(writer_task.await?)
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ExecutionStatusView, ReceiptEnumView,
+};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+/// Aurora Engine account, the same on mainnet and testnet
+pub(crate) const AURORA_ACCOUNT: &str = "aurora";
+
+/// Result of the Aurora Engine `submit` call as it is returned
+/// (borsh-serialized) in the execution outcome of the receipt.
+/// Aurora doesn't store the EVM receipts in its state,
+/// that's why we read the logs from the outcome
+#[derive(BorshDeserialize)]
+struct SubmitResult {
+    _version: u8,
+    _status: TransactionStatus,
+    _gas_used: u64,
+    logs: Vec<ResultLog>,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum TransactionStatus {
+    Succeed(Vec<u8>),
+    Revert(Vec<u8>),
+    OutOfGas,
+    OutOfFund,
+    OutOfOffset,
+    CallTooDeep,
+}
+
+#[derive(BorshDeserialize)]
+struct ResultLog {
+    address: [u8; 20],
+    topics: Vec<[u8; 32]>,
+    data: Vec<u8>,
+}
+
+/// EVM event log emitted by the Aurora transaction
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct AuroraEvmEvent {
+    pub evm_address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    /// Name and the decoded params of the event if it is known from the ABI file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Extracts the EVM event logs from the executed `submit` receipt
+/// of the Aurora Engine
+pub(crate) fn evm_events(
+    outcome: &IndexerExecutionOutcomeWithReceipt,
+    abi: Option<&ethabi::Contract>,
+) -> Vec<AuroraEvmEvent> {
+    if outcome.receipt.receiver_id.as_ref() != AURORA_ACCOUNT || !is_submit(outcome) {
+        return vec![];
+    }
+    let submit_result = match &outcome.execution_outcome.outcome.status {
+        ExecutionStatusView::SuccessValue(value) => base64::decode(value)
+            .ok()
+            .and_then(|bytes| SubmitResult::try_from_slice(&bytes).ok()),
+        _ => None,
+    };
+
+    submit_result
+        .map(|submit_result| {
+            submit_result
+                .logs
+                .into_iter()
+                .map(|log| decode_log(log, abi))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_submit(outcome: &IndexerExecutionOutcomeWithReceipt) -> bool {
+    match &outcome.receipt.receipt {
+        ReceiptEnumView::Action { actions, .. } => actions.iter().any(|action| {
+            matches!(action, ActionView::FunctionCall { method_name, .. } if method_name == "submit")
+        }),
+        ReceiptEnumView::Data { .. } => false,
+    }
+}
+
+fn decode_log(log: ResultLog, abi: Option<&ethabi::Contract>) -> AuroraEvmEvent {
+    let mut event = AuroraEvmEvent {
+        evm_address: format!("0x{}", hex::encode(log.address)),
+        topics: log
+            .topics
+            .iter()
+            .map(|topic| format!("0x{}", hex::encode(topic)))
+            .collect(),
+        data: format!("0x{}", hex::encode(&log.data)),
+        event_name: None,
+        params: None,
+    };
+
+    // The first topic is the event signature, we look for it in the ABI
+    let abi_event = match (abi, log.topics.first()) {
+        (Some(abi), Some(signature)) => abi
+            .events()
+            .find(|abi_event| abi_event.signature().as_bytes() == signature),
+        _ => None,
+    };
+    if let Some(abi_event) = abi_event {
+        let raw_log = ethabi::RawLog {
+            topics: log.topics.iter().map(ethabi::Hash::from).collect(),
+            data: log.data,
+        };
+        if let Ok(parsed_log) = abi_event.parse_log(raw_log) {
+            event.event_name = Some(abi_event.name.clone());
+            event.params = Some(
+                parsed_log
+                    .params
+                    .into_iter()
+                    .map(|param| (param.name, param.value.to_string().into()))
+                    .collect(),
+            );
+        }
+    }
+
+    event
+}
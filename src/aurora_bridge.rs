@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+
+use crate::aurora::AURORA_ACCOUNT;
+use crate::ft::FtTransferEvent;
+
+/// Every Aurora Engine storage key starts with the version prefix and the key prefix,
+/// see BRIDGE_STORAGE.md
+const VERSION_PREFIX: u8 = 0x07;
+const NEP141_ERC20_MAP_PREFIX: u8 = 0x08;
+
+/// NEP-141 tokens moved into Aurora (lock) or back out of it (unlock)
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct AuroraBridgeEvent {
+    #[serde(skip)]
+    pub event_type: &'static str,
+    pub nep141_token: String,
+    /// The ERC-20 contract mirroring the token, if its mapping was seen during the run
+    pub erc20_address: Option<String>,
+    /// The NEAR account the tokens came from (lock) or went to (unlock)
+    pub near_account_id: String,
+    pub amount: String,
+    pub block_height: BlockHeight,
+}
+
+/// Recognizes the bridge transfers by the NEP-141 `ft_transfer` events to and from
+/// the `aurora` account. The locked tokens are held in the balances of the token
+/// contracts, so the Aurora storage only tells us which ERC-20 mirrors the token
+#[derive(Debug, Default)]
+pub(crate) struct AuroraBridgeDecoder {
+    /// NEP-141 token account id to the hex ERC-20 address
+    erc20_addresses: HashMap<String, String>,
+}
+
+impl AuroraBridgeDecoder {
+    /// Remembers the ERC-20 address if the `aurora` storage entry is the
+    /// NEP-141 to ERC-20 map: `[0x07, 0x08, nep141 account id]` => 20-byte address
+    pub(crate) fn record_mapping(&mut self, key: &[u8], value: &[u8]) {
+        if let [VERSION_PREFIX, NEP141_ERC20_MAP_PREFIX, nep141_token @ ..] = key {
+            if let (Ok(nep141_token), 20) = (std::str::from_utf8(nep141_token), value.len()) {
+                self.erc20_addresses.insert(
+                    nep141_token.to_string(),
+                    format!("0x{}", hex::encode(value)),
+                );
+            }
+        }
+    }
+
+    pub(crate) fn decode_transfer(
+        &self,
+        transfer: &FtTransferEvent,
+        block_height: BlockHeight,
+    ) -> Option<AuroraBridgeEvent> {
+        let (event_type, near_account_id) = if transfer.new_owner_id == AURORA_ACCOUNT {
+            ("aurora_bridge_lock", &transfer.old_owner_id)
+        } else if transfer.old_owner_id == AURORA_ACCOUNT {
+            ("aurora_bridge_unlock", &transfer.new_owner_id)
+        } else {
+            return None;
+        };
+        let nep141_token = transfer.token_contract.to_string();
+        Some(AuroraBridgeEvent {
+            event_type,
+            erc20_address: self.erc20_addresses.get(&nep141_token).cloned(),
+            nep141_token,
+            near_account_id: near_account_id.clone(),
+            amount: transfer.amount.clone(),
+            block_height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERC20_ADDRESS: [u8; 20] = [0xab; 20];
+
+    fn mapping_key(nep141_token: &[u8]) -> Vec<u8> {
+        [&[VERSION_PREFIX, NEP141_ERC20_MAP_PREFIX], nep141_token].concat()
+    }
+
+    fn transfer(old_owner_id: &str, new_owner_id: &str) -> FtTransferEvent {
+        FtTransferEvent {
+            token_contract: "usdt.tether-token.near".parse().unwrap(),
+            old_owner_id: old_owner_id.to_string(),
+            new_owner_id: new_owner_id.to_string(),
+            amount: "1000000".to_string(),
+            memo: None,
+            symbol: None,
+            amount_human: None,
+        }
+    }
+
+    #[test]
+    fn records_erc20_mapping() {
+        let mut decoder = AuroraBridgeDecoder::default();
+        decoder.record_mapping(&mapping_key(b"usdt.tether-token.near"), &ERC20_ADDRESS);
+        assert_eq!(
+            decoder.erc20_addresses.get("usdt.tether-token.near"),
+            Some(&format!("0x{}", "ab".repeat(20)))
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_mapping_entries() {
+        let mut decoder = AuroraBridgeDecoder::default();
+        // Another storage prefix
+        decoder.record_mapping(&[VERSION_PREFIX, 0x09, b'a'], &ERC20_ADDRESS);
+        // The key doesn't even have the prefixes
+        decoder.record_mapping(&[VERSION_PREFIX], &ERC20_ADDRESS);
+        // The account id isn't UTF-8
+        decoder.record_mapping(&mapping_key(&[0xff, 0xfe]), &ERC20_ADDRESS);
+        // Truncated and overlong addresses
+        decoder.record_mapping(&mapping_key(b"a.near"), &ERC20_ADDRESS[..19]);
+        decoder.record_mapping(&mapping_key(b"b.near"), &[0xab; 21]);
+        assert!(decoder.erc20_addresses.is_empty());
+    }
+
+    #[test]
+    fn decodes_lock() {
+        let mut decoder = AuroraBridgeDecoder::default();
+        decoder.record_mapping(&mapping_key(b"usdt.tether-token.near"), &ERC20_ADDRESS);
+        let event = decoder
+            .decode_transfer(&transfer("alice.near", AURORA_ACCOUNT), 42)
+            .unwrap();
+        assert_eq!(event.event_type, "aurora_bridge_lock");
+        assert_eq!(event.near_account_id, "alice.near");
+        assert_eq!(event.nep141_token, "usdt.tether-token.near");
+        assert_eq!(event.erc20_address, Some(format!("0x{}", "ab".repeat(20))));
+        assert_eq!(event.amount, "1000000");
+        assert_eq!(event.block_height, 42);
+    }
+
+    #[test]
+    fn decodes_unlock_without_known_mapping() {
+        let decoder = AuroraBridgeDecoder::default();
+        let event = decoder
+            .decode_transfer(&transfer(AURORA_ACCOUNT, "bob.near"), 42)
+            .unwrap();
+        assert_eq!(event.event_type, "aurora_bridge_unlock");
+        assert_eq!(event.near_account_id, "bob.near");
+        assert_eq!(event.erc20_address, None);
+    }
+
+    #[test]
+    fn ignores_transfers_not_involving_aurora() {
+        let decoder = AuroraBridgeDecoder::default();
+        assert!(decoder
+            .decode_transfer(&transfer("alice.near", "bob.near"), 42)
+            .is_none());
+    }
+}
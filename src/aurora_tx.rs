@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+use near_lake_framework::near_indexer_primitives::views::{ActionView, ReceiptEnumView};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+use sha3::{Digest, Keccak256};
+
+use crate::aurora::AURORA_ACCOUNT;
+
+/// EIP-2718 transaction types, the legacy transactions have no type byte
+const EIP2930_TX_TYPE: u8 = 0x01;
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// EVM transaction submitted to the Aurora Engine
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct AuroraEvmTx {
+    /// `legacy`, `eip2930` or `eip1559`
+    pub tx_type: &'static str,
+    /// The signer recovered from the signature, `None` if the signature is invalid
+    pub from: Option<String>,
+    /// `None` for a contract deployment
+    pub to: Option<String>,
+    /// Wei as a decimal string, it doesn't fit into JSON number
+    pub value: String,
+    pub data: String,
+    pub receipt_id: String,
+    pub block_height: BlockHeight,
+}
+
+/// The fields of the transaction we read and the preimage of the signature
+struct DecodedTx<'a> {
+    tx_type: &'static str,
+    to: &'a [u8],
+    value: &'a [u8],
+    data: &'a [u8],
+    signing_hash: [u8; 32],
+    recovery_id: i32,
+    r: &'a [u8],
+    s: &'a [u8],
+}
+
+/// Decodes the RLP-encoded EVM transactions from the args of the `submit` calls.
+/// The sender isn't part of the transaction, we recover it from the signature
+pub(crate) struct AuroraTxDecoder {
+    secp: secp256k1::Secp256k1,
+    /// Lowercase `0x` addresses from the `--aurora-watch-evm-address`,
+    /// all the transactions are printed if it is empty
+    watched_addresses: HashSet<String>,
+}
+
+impl AuroraTxDecoder {
+    pub(crate) fn new(watched_addresses: &[String]) -> anyhow::Result<Self> {
+        let watched_addresses = watched_addresses
+            .iter()
+            .map(|address| {
+                let address = address.to_lowercase();
+                let hex_address = address.strip_prefix("0x").unwrap_or(&address);
+                match hex::decode(hex_address) {
+                    Ok(bytes) if bytes.len() == 20 => Ok(format!("0x{}", hex_address)),
+                    _ => anyhow::bail!("Invalid EVM address {}", address),
+                }
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            secp: secp256k1::Secp256k1::new(),
+            watched_addresses,
+        })
+    }
+
+    /// Returns the watched EVM transactions submitted by the receipt
+    pub(crate) fn transactions(
+        &self,
+        outcome: &IndexerExecutionOutcomeWithReceipt,
+        block_height: BlockHeight,
+    ) -> Vec<AuroraEvmTx> {
+        if outcome.receipt.receiver_id.as_ref() != AURORA_ACCOUNT {
+            return vec![];
+        }
+        let actions = match &outcome.receipt.receipt {
+            ReceiptEnumView::Action { actions, .. } => actions,
+            ReceiptEnumView::Data { .. } => return vec![],
+        };
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                ActionView::FunctionCall {
+                    method_name, args, ..
+                } if method_name == "submit" => base64::decode(args).ok(),
+                _ => None,
+            })
+            .filter_map(|tx_bytes| {
+                let tx = decode_tx(&tx_bytes)?;
+                let event = AuroraEvmTx {
+                    tx_type: tx.tx_type,
+                    from: self.recover_sender(&tx),
+                    to: (!tx.to.is_empty()).then(|| format!("0x{}", hex::encode(tx.to))),
+                    value: (tx.value.len() <= 32)
+                        .then(|| ethabi::Uint::from_big_endian(tx.value).to_string())?,
+                    data: format!("0x{}", hex::encode(tx.data)),
+                    receipt_id: outcome.receipt.receipt_id.to_string(),
+                    block_height,
+                };
+                self.is_watched(&event).then(|| event)
+            })
+            .collect()
+    }
+
+    fn is_watched(&self, event: &AuroraEvmTx) -> bool {
+        self.watched_addresses.is_empty()
+            || [&event.from, &event.to]
+                .iter()
+                .filter_map(|address| address.as_ref())
+                .any(|address| self.watched_addresses.contains(address))
+    }
+
+    fn recover_sender(&self, tx: &DecodedTx) -> Option<String> {
+        // r and s are big-endian numbers with the leading zeros stripped
+        if tx.r.len() > 32 || tx.s.len() > 32 {
+            return None;
+        }
+        let mut compact_signature = [0u8; 64];
+        compact_signature[32 - tx.r.len()..32].copy_from_slice(tx.r);
+        compact_signature[64 - tx.s.len()..].copy_from_slice(tx.s);
+        let recovery_id = secp256k1::RecoveryId::from_i32(tx.recovery_id).ok()?;
+        let signature = secp256k1::RecoverableSignature::from_compact(
+            &self.secp,
+            &compact_signature,
+            recovery_id,
+        )
+        .ok()?;
+        let message = secp256k1::Message::from_slice(&tx.signing_hash).ok()?;
+        let public_key = self.secp.recover(&message, &signature).ok()?;
+        // The address is the last 20 bytes of the hash of the uncompressed key
+        // without its 0x04 prefix
+        let public_key = public_key.serialize_vec(&self.secp, false);
+        let hash = Keccak256::digest(&public_key[1..]);
+        Some(format!("0x{}", hex::encode(&hash[12..])))
+    }
+}
+
+fn decode_tx(tx_bytes: &[u8]) -> Option<DecodedTx<'_>> {
+    match *tx_bytes.first()? {
+        EIP2930_TX_TYPE => decode_typed_tx("eip2930", tx_bytes, 11, 4),
+        EIP1559_TX_TYPE => decode_typed_tx("eip1559", tx_bytes, 12, 5),
+        _ => decode_legacy_tx(tx_bytes),
+    }
+}
+
+/// `[nonce, gasPrice, gasLimit, to, value, data, v, r, s]`, the chain id is
+/// encoded into `v` since EIP-155
+fn decode_legacy_tx(tx_bytes: &[u8]) -> Option<DecodedTx<'_>> {
+    let rlp = rlp::Rlp::new(tx_bytes);
+    if !rlp.is_list() || rlp.item_count().ok()? != 9 {
+        return None;
+    }
+    let v: u64 = rlp.val_at(6).ok()?;
+    let chain_id = match v {
+        27 | 28 => None,
+        v if v >= 35 => Some((v - 35) / 2),
+        _ => return None,
+    };
+    // EIP-155 signs `[..6 fields, chainId, 0, 0]` instead of the 6 fields
+    let mut stream = rlp::RlpStream::new_list(if chain_id.is_some() { 9 } else { 6 });
+    for index in 0..6 {
+        stream.append_raw(rlp.at(index).ok()?.as_raw(), 1);
+    }
+    let recovery_id = match chain_id {
+        Some(chain_id) => {
+            stream.append(&chain_id);
+            stream.append_empty_data();
+            stream.append_empty_data();
+            (v - 35) % 2
+        }
+        None => v - 27,
+    };
+    Some(DecodedTx {
+        tx_type: "legacy",
+        to: rlp.at(3).ok()?.data().ok()?,
+        value: rlp.at(4).ok()?.data().ok()?,
+        data: rlp.at(5).ok()?.data().ok()?,
+        signing_hash: Keccak256::digest(stream.out()).into(),
+        recovery_id: recovery_id as i32,
+        r: rlp.at(7).ok()?.data().ok()?,
+        s: rlp.at(8).ok()?.data().ok()?,
+    })
+}
+
+/// `type || rlp([..fields, yParity, r, s])` where `to`, `value` and `data` follow
+/// each other starting at `to_index`, the signature covers everything but its own fields
+fn decode_typed_tx<'a>(
+    tx_type: &'static str,
+    tx_bytes: &'a [u8],
+    item_count: usize,
+    to_index: usize,
+) -> Option<DecodedTx<'a>> {
+    let rlp = rlp::Rlp::new(&tx_bytes[1..]);
+    if !rlp.is_list() || rlp.item_count().ok()? != item_count {
+        return None;
+    }
+    let signed_fields = item_count - 3;
+    let mut stream = rlp::RlpStream::new_list(signed_fields);
+    for index in 0..signed_fields {
+        stream.append_raw(rlp.at(index).ok()?.as_raw(), 1);
+    }
+    let mut hasher = Keccak256::new();
+    hasher.update([tx_bytes[0]]);
+    hasher.update(stream.out());
+    let y_parity: u8 = rlp.val_at(signed_fields).ok()?;
+    Some(DecodedTx {
+        tx_type,
+        to: rlp.at(to_index).ok()?.data().ok()?,
+        value: rlp.at(to_index + 1).ok()?.data().ok()?,
+        data: rlp.at(to_index + 2).ok()?.data().ok()?,
+        signing_hash: hasher.finalize().into(),
+        recovery_id: y_parity.into(),
+        r: rlp.at(signed_fields + 1).ok()?.data().ok()?,
+        s: rlp.at(signed_fields + 2).ok()?.data().ok()?,
+    })
+}
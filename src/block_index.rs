@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ReceiptEnumView, StateChangeCauseView,
+};
+use near_lake_framework::near_indexer_primitives::{
+    CryptoHash, IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome, StreamerMessage,
+};
+
+/// In-block index of the transactions and the executed receipts
+/// so we can find what caused a StateChange without scanning
+/// all the shards for every change
+pub(crate) struct BlockIndex<'a> {
+    pub transactions: HashMap<CryptoHash, &'a IndexerTransactionWithOutcome>,
+    pub receipts: HashMap<CryptoHash, &'a IndexerExecutionOutcomeWithReceipt>,
+    /// The transaction each receipt produced in this block originates from
+    pub originating_transactions: HashMap<CryptoHash, CryptoHash>,
+}
+
+impl<'a> BlockIndex<'a> {
+    pub(crate) fn new(streamer_message: &'a StreamerMessage) -> Self {
+        let mut transactions = HashMap::new();
+        let mut receipts = HashMap::new();
+        let mut originating_transactions = HashMap::new();
+        for shard in &streamer_message.shards {
+            if let Some(chunk) = &shard.chunk {
+                for transaction in &chunk.transactions {
+                    transactions.insert(transaction.transaction.hash, transaction);
+                    for receipt_id in &transaction.outcome.execution_outcome.outcome.receipt_ids {
+                        originating_transactions.insert(*receipt_id, transaction.transaction.hash);
+                    }
+                }
+            }
+            for outcome in &shard.receipt_execution_outcomes {
+                receipts.insert(outcome.receipt.receipt_id, outcome);
+            }
+        }
+        // The receipts executed in the same block may produce more receipts,
+        // those belong to the same transaction. The shards aren't ordered
+        // by the execution, so we repeat until nothing new is found
+        loop {
+            let mut is_found = false;
+            for outcome in receipts.values() {
+                let tx_hash = match originating_transactions.get(&outcome.receipt.receipt_id) {
+                    Some(tx_hash) => *tx_hash,
+                    None => continue,
+                };
+                for receipt_id in &outcome.execution_outcome.outcome.receipt_ids {
+                    if originating_transactions
+                        .insert(*receipt_id, tx_hash)
+                        .is_none()
+                    {
+                        is_found = true;
+                    }
+                }
+            }
+            if !is_found {
+                break;
+            }
+        }
+        Self {
+            transactions,
+            receipts,
+            originating_transactions,
+        }
+    }
+
+    /// Resolves the details of the transaction or the receipt
+    /// the StateChange was caused by if it is in this block
+    pub(crate) fn cause_context(&self, cause: &StateChangeCauseView) -> Option<CauseContext> {
+        match cause {
+            StateChangeCauseView::TransactionProcessing { tx_hash } => {
+                let transaction = &self.transactions.get(tx_hash)?.transaction;
+                Some(CauseContext::Transaction {
+                    signer_id: transaction.signer_id.clone(),
+                    receiver_id: transaction.receiver_id.clone(),
+                    actions: transaction.actions.iter().map(action_summary).collect(),
+                })
+            }
+            StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+            | StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+            | StateChangeCauseView::ReceiptProcessing { receipt_hash }
+            | StateChangeCauseView::PostponedReceipt { receipt_hash } => {
+                let receipt = &self.receipts.get(receipt_hash)?.receipt;
+                Some(CauseContext::Receipt {
+                    predecessor_id: receipt.predecessor_id.clone(),
+                    receiver_id: receipt.receiver_id.clone(),
+                    actions: match &receipt.receipt {
+                        ReceiptEnumView::Action { actions, .. } => {
+                            actions.iter().map(action_summary).collect()
+                        }
+                        ReceiptEnumView::Data { .. } => vec![],
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The receipt the StateChange was caused by, if it was caused by one
+pub(crate) fn cause_receipt_hash(cause: &StateChangeCauseView) -> Option<&CryptoHash> {
+    match cause {
+        StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+        | StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+        | StateChangeCauseView::ReceiptProcessing { receipt_hash }
+        | StateChangeCauseView::PostponedReceipt { receipt_hash } => Some(receipt_hash),
+        _ => None,
+    }
+}
+
+/// Details of the transaction or the receipt that caused the StateChange
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum CauseContext {
+    Transaction {
+        signer_id: AccountId,
+        receiver_id: AccountId,
+        actions: Vec<String>,
+    },
+    Receipt {
+        predecessor_id: AccountId,
+        receiver_id: AccountId,
+        actions: Vec<String>,
+    },
+}
+
+impl CauseContext {
+    /// The account that has sent the transaction or the receipt
+    pub(crate) fn sender_id(&self) -> &AccountId {
+        match self {
+            CauseContext::Transaction { signer_id, .. } => signer_id,
+            CauseContext::Receipt { predecessor_id, .. } => predecessor_id,
+        }
+    }
+}
+
+/// Short human-readable description of the action
+fn action_summary(action: &ActionView) -> String {
+    match action {
+        ActionView::CreateAccount => "CreateAccount".to_string(),
+        ActionView::DeployContract { .. } => "DeployContract".to_string(),
+        ActionView::FunctionCall {
+            method_name,
+            deposit,
+            ..
+        } => format!("FunctionCall({}, deposit {})", method_name, deposit),
+        ActionView::Transfer { deposit } => format!("Transfer({})", deposit),
+        ActionView::Stake { stake, .. } => format!("Stake({})", stake),
+        ActionView::AddKey { public_key, .. } => format!("AddKey({})", public_key),
+        ActionView::DeleteKey { public_key } => format!("DeleteKey({})", public_key),
+        ActionView::DeleteAccount { beneficiary_id } => {
+            format!("DeleteAccount({})", beneficiary_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithOptionalReceipt;
+    use serde_json::json;
+
+    use super::*;
+
+    const PUBLIC_KEY: &str = "ed25519:11111111111111111111111111111111";
+
+    fn outcome(id: CryptoHash, executor_id: &str) -> serde_json::Value {
+        json!({
+            "proof": [],
+            "block_hash": CryptoHash::default().to_string(),
+            "id": id.to_string(),
+            "outcome": {
+                "logs": [],
+                "receipt_ids": [],
+                "gas_burnt": 0,
+                "tokens_burnt": "0",
+                "executor_id": executor_id,
+                "status": {"SuccessValue": ""},
+            },
+        })
+    }
+
+    fn transaction(tx_hash: CryptoHash) -> IndexerTransactionWithOutcome {
+        IndexerTransactionWithOutcome {
+            transaction: serde_json::from_value(json!({
+                "signer_id": "alice.near",
+                "public_key": PUBLIC_KEY,
+                "nonce": 1,
+                "receiver_id": "token.near",
+                "actions": [{"Transfer": {"deposit": "5"}}],
+                "signature": format!("ed25519:{}", "1".repeat(64)),
+                "hash": tx_hash.to_string(),
+            }))
+            .unwrap(),
+            outcome: IndexerExecutionOutcomeWithOptionalReceipt {
+                execution_outcome: serde_json::from_value(outcome(tx_hash, "alice.near")).unwrap(),
+                receipt: None,
+            },
+        }
+    }
+
+    fn receipt(receipt_id: CryptoHash) -> IndexerExecutionOutcomeWithReceipt {
+        IndexerExecutionOutcomeWithReceipt {
+            execution_outcome: serde_json::from_value(outcome(receipt_id, "token.near")).unwrap(),
+            receipt: serde_json::from_value(json!({
+                "predecessor_id": "alice.near",
+                "receiver_id": "token.near",
+                "receipt_id": receipt_id.to_string(),
+                "receipt": {"Action": {
+                    "signer_id": "alice.near",
+                    "signer_public_key": PUBLIC_KEY,
+                    "gas_price": "100000000",
+                    "output_data_receivers": [],
+                    "input_data_ids": [],
+                    "actions": [{"FunctionCall": {
+                        "method_name": "ft_transfer",
+                        "args": "",
+                        "gas": 30000000000000u64,
+                        "deposit": "1",
+                    }}],
+                }},
+            }))
+            .unwrap(),
+        }
+    }
+
+    fn block_index<'a>(
+        transaction: &'a IndexerTransactionWithOutcome,
+        receipt: &'a IndexerExecutionOutcomeWithReceipt,
+    ) -> BlockIndex<'a> {
+        BlockIndex {
+            transactions: HashMap::from([(transaction.transaction.hash, transaction)]),
+            receipts: HashMap::from([(receipt.receipt.receipt_id, receipt)]),
+            originating_transactions: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn resolves_transaction_cause() {
+        let (transaction, receipt) = (
+            transaction(CryptoHash([1; 32])),
+            receipt(CryptoHash([2; 32])),
+        );
+        let index = block_index(&transaction, &receipt);
+        let context = index
+            .cause_context(&StateChangeCauseView::TransactionProcessing {
+                tx_hash: CryptoHash([1; 32]),
+            })
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&context).unwrap(),
+            json!({
+                "type": "transaction",
+                "signer_id": "alice.near",
+                "receiver_id": "token.near",
+                "actions": ["Transfer(5)"],
+            })
+        );
+    }
+
+    #[test]
+    fn resolves_receipt_cause() {
+        let (transaction, receipt) = (
+            transaction(CryptoHash([1; 32])),
+            receipt(CryptoHash([2; 32])),
+        );
+        let index = block_index(&transaction, &receipt);
+        let context = index
+            .cause_context(&StateChangeCauseView::ReceiptProcessing {
+                receipt_hash: CryptoHash([2; 32]),
+            })
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&context).unwrap(),
+            json!({
+                "type": "receipt",
+                "predecessor_id": "alice.near",
+                "receiver_id": "token.near",
+                "actions": ["FunctionCall(ft_transfer, deposit 1)"],
+            })
+        );
+        assert_eq!(context.sender_id().as_ref(), "alice.near");
+    }
+
+    #[test]
+    fn resolves_action_receipt_gas_reward_cause() {
+        let (transaction, receipt) = (
+            transaction(CryptoHash([1; 32])),
+            receipt(CryptoHash([2; 32])),
+        );
+        let index = block_index(&transaction, &receipt);
+        let cause = StateChangeCauseView::ActionReceiptGasReward {
+            receipt_hash: CryptoHash([2; 32]),
+        };
+        assert!(matches!(
+            index.cause_context(&cause),
+            Some(CauseContext::Receipt { .. })
+        ));
+        assert_eq!(cause_receipt_hash(&cause), Some(&CryptoHash([2; 32])));
+    }
+
+    #[test]
+    fn has_no_context_for_other_causes() {
+        let (transaction, receipt) = (
+            transaction(CryptoHash([1; 32])),
+            receipt(CryptoHash([2; 32])),
+        );
+        let index = block_index(&transaction, &receipt);
+        assert!(index
+            .cause_context(&StateChangeCauseView::NotWritableToDisk)
+            .is_none());
+        assert!(index
+            .cause_context(&StateChangeCauseView::ValidatorAccountsUpdate)
+            .is_none());
+        assert!(cause_receipt_hash(&StateChangeCauseView::NotWritableToDisk).is_none());
+    }
+
+    #[test]
+    fn has_no_context_for_causes_outside_the_block() {
+        let (transaction, receipt) = (
+            transaction(CryptoHash([1; 32])),
+            receipt(CryptoHash([2; 32])),
+        );
+        let index = block_index(&transaction, &receipt);
+        assert!(index
+            .cause_context(&StateChangeCauseView::TransactionProcessing {
+                tx_hash: CryptoHash([3; 32]),
+            })
+            .is_none());
+        assert!(index
+            .cause_context(&StateChangeCauseView::ReceiptProcessing {
+                receipt_hash: CryptoHash([3; 32]),
+            })
+            .is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+use near_lake_framework::near_indexer_primitives::{IndexerShard, StreamerMessage};
+
+/// Block heights from the `--block-range-list` file in ascending order.
+/// The file has a height (`HEIGHT`) or an inclusive range (`START-END`) per line,
+/// the ranges may overlap and come in any order
+#[derive(Debug, Clone)]
+pub(crate) struct BlockRangeIterator {
+    /// Merged ranges, the lowest first
+    ranges: std::collections::VecDeque<(BlockHeight, BlockHeight)>,
+}
+
+impl BlockRangeIterator {
+    pub(crate) fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", path.display(), err))?;
+        let mut ranges = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let range = match line.split_once('-') {
+                    Some((start, end)) => (start.trim().parse()?, end.trim().parse()?),
+                    None => {
+                        let height = line.parse()?;
+                        (height, height)
+                    }
+                };
+                Ok(range)
+            })
+            .collect::<Result<Vec<(BlockHeight, BlockHeight)>, std::num::ParseIntError>>()
+            .map_err(|err| anyhow::anyhow!("Invalid block range in {}: {}", path.display(), err))?;
+        if let Some((start, end)) = ranges.iter().find(|(start, end)| start > end) {
+            anyhow::bail!(
+                "Invalid block range {}-{} in {}",
+                start,
+                end,
+                path.display()
+            );
+        }
+        ranges.sort_unstable();
+        let mut merged: std::collections::VecDeque<(BlockHeight, BlockHeight)> =
+            std::collections::VecDeque::new();
+        for (start, end) in ranges {
+            match merged.back_mut() {
+                Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                    *last_end = (*last_end).max(end)
+                }
+                _ => merged.push_back((start, end)),
+            }
+        }
+        Ok(Self { ranges: merged })
+    }
+
+    pub(crate) fn first(&self) -> Option<BlockHeight> {
+        self.ranges.front().map(|(start, _)| *start)
+    }
+}
+
+impl Iterator for BlockRangeIterator {
+    type Item = BlockHeight;
+
+    fn next(&mut self) -> Option<BlockHeight> {
+        let (start, end) = self.ranges.front_mut()?;
+        let height = *start;
+        if start == end {
+            self.ranges.pop_front();
+        } else {
+            *start += 1;
+        }
+        Some(height)
+    }
+}
+
+/// Fetches the listed blocks one by one instead of streaming a contiguous range.
+/// NEAR skips heights, the listed heights without a block are reported and skipped
+pub(crate) fn block_list_stream(
+    client: aws_sdk_s3::Client,
+    s3_bucket_name: String,
+    blocks: BlockRangeIterator,
+    verify_checksums: bool,
+    retry_policy: crate::retry::RetryPolicy,
+) -> tokio::sync::mpsc::Receiver<StreamerMessage> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    tokio::spawn(async move {
+        let mut checksum_failures_total = 0u64;
+        for height in blocks {
+            // A checksum mismatch is retried too, it may be a partial read
+            let result = retry_policy
+                .run(|| fetch_streamer_message(&client, &s3_bucket_name, height, verify_checksums))
+                .await;
+            match result {
+                Ok(streamer_message) => {
+                    if sender.send(streamer_message).await.is_err() {
+                        break;
+                    }
+                }
+                Err(err) => report_skipped_block(height, err, &mut checksum_failures_total),
+            }
+        }
+    });
+    receiver
+}
+
+/// Reports the block we have failed to fetch, the checksum mismatches
+/// (with `--verify-checksums`) are printed as events for the monitoring
+pub(crate) fn report_skipped_block(
+    height: BlockHeight,
+    err: anyhow::Error,
+    checksum_failures_total: &mut u64,
+) {
+    match err.downcast::<crate::s3::ChecksumMismatch>() {
+        Ok(mismatch) => {
+            *checksum_failures_total += 1;
+            crate::print_event(
+                height,
+                "checksum_mismatch",
+                &serde_json::json!({
+                    "key": mismatch.key,
+                    "etag": mismatch.etag,
+                    "md5": mismatch.md5,
+                    "checksum_failures_total": checksum_failures_total,
+                }),
+            );
+        }
+        Err(err) => eprintln!("Skipping block #{}: {}", height, err),
+    }
+}
+
+pub(crate) async fn fetch_streamer_message(
+    client: &aws_sdk_s3::Client,
+    s3_bucket_name: &str,
+    height: BlockHeight,
+    verify_checksums: bool,
+) -> anyhow::Result<StreamerMessage> {
+    let block = crate::s3::fetch_block(client, s3_bucket_name, height, verify_checksums).await?;
+    let mut shards: Vec<IndexerShard> = Vec::with_capacity(block.chunks.len());
+    for chunk in &block.chunks {
+        shards.push(
+            crate::s3::fetch_shard(
+                client,
+                s3_bucket_name,
+                height,
+                chunk.shard_id,
+                verify_checksums,
+            )
+            .await?,
+        );
+    }
+    Ok(StreamerMessage { block, shards })
+}
@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+
+/// Number of the latest block gaps the rolling average is computed over
+const ROLLING_WINDOW: usize = 100;
+
+/// The block took too long to arrive after the previous one,
+/// or the blocks have been slow on average
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct BlockTimeAlert {
+    /// `gap` or `rolling_average`
+    pub reason: &'static str,
+    pub gap_ms: u64,
+    pub rolling_average_ms: u64,
+    pub prev_height: BlockHeight,
+    pub current_height: BlockHeight,
+}
+
+/// Compares the timestamps of the consecutive blocks
+#[derive(Debug)]
+pub(crate) struct BlockTimeTracker {
+    threshold_ms: u64,
+    last_timestamp: Option<u64>,
+    last_height: BlockHeight,
+    gaps_ms: VecDeque<u64>,
+    /// The rolling average alert fires once until the average is back under the limit
+    is_average_alerted: bool,
+}
+
+impl BlockTimeTracker {
+    pub(crate) fn new(threshold_ms: u64) -> Self {
+        Self {
+            threshold_ms,
+            last_timestamp: None,
+            last_height: 0,
+            gaps_ms: VecDeque::with_capacity(ROLLING_WINDOW),
+            is_average_alerted: false,
+        }
+    }
+
+    /// Returns the alert if the gap since the previous block exceeds the threshold
+    /// or the rolling average exceeds twice the threshold
+    pub(crate) fn check(
+        &mut self,
+        height: BlockHeight,
+        timestamp_ns: u64,
+    ) -> Option<BlockTimeAlert> {
+        let prev_height = std::mem::replace(&mut self.last_height, height);
+        let last_timestamp = self.last_timestamp.replace(timestamp_ns)?;
+        let gap_ms = timestamp_ns.saturating_sub(last_timestamp) / 1_000_000;
+
+        if self.gaps_ms.len() == ROLLING_WINDOW {
+            self.gaps_ms.pop_front();
+        }
+        self.gaps_ms.push_back(gap_ms);
+        let rolling_average_ms = self.gaps_ms.iter().sum::<u64>() / self.gaps_ms.len() as u64;
+
+        let is_average_exceeded = rolling_average_ms > 2 * self.threshold_ms;
+        let is_average_crossed = is_average_exceeded && !self.is_average_alerted;
+        self.is_average_alerted = is_average_exceeded;
+
+        let reason = if gap_ms > self.threshold_ms {
+            "gap"
+        } else if is_average_crossed {
+            "rolling_average"
+        } else {
+            return None;
+        };
+        Some(BlockTimeAlert {
+            reason,
+            gap_ms,
+            rolling_average_ms,
+            prev_height,
+            current_height: height,
+        })
+    }
+}
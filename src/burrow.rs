@@ -0,0 +1,57 @@
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+use crate::events;
+
+/// Burrow lending protocol contract on mainnet
+pub(crate) const BURROW_ACCOUNT: &str = "contract.main.burrow.near";
+
+/// Burrow position change. The contract doesn't let us decode
+/// the positions from its storage reliably, but it logs every position
+/// change as a NEP-297 event, so we build our events from those
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct BurrowEvent {
+    /// Original Burrow event name (e.g. `increase_collateral`, `repay`)
+    pub action: String,
+    /// Only an object can be flattened into the event
+    #[serde(flatten)]
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Returns the position changes logged by the executed Burrow receipt
+/// along with the event type for each of them
+pub(crate) fn position_events(
+    outcome: &IndexerExecutionOutcomeWithReceipt,
+) -> Vec<(&'static str, BurrowEvent)> {
+    if outcome.receipt.receiver_id.as_ref() != BURROW_ACCOUNT {
+        return vec![];
+    }
+    events::receipt_events(outcome)
+        .into_iter()
+        .filter(|event| event.standard == "burrow")
+        .filter_map(|event| {
+            let event_type = match event.event.as_str() {
+                "increase_collateral" | "decrease_collateral" => "burrow_collateral_change",
+                "borrow" | "repay" => "burrow_borrow_change",
+                "liquidate" | "force_close" => "burrow_liquidation",
+                _ => return None,
+            };
+            // Burrow puts the list of the changes in the `data`
+            let data = match event.data {
+                serde_json::Value::Array(data) => data,
+                data => vec![data],
+            };
+            // The log is the contract's output, we skip the changes that aren't objects
+            Some(data.into_iter().filter_map(move |data| match data {
+                serde_json::Value::Object(data) => Some((
+                    event_type,
+                    BurrowEvent {
+                        action: event.event.clone(),
+                        data,
+                    },
+                )),
+                _ => None,
+            }))
+        })
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,181 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{ActionView, ReceiptEnumView};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+use sha3::{Digest, Sha3_256};
+
+/// The MPC contract derives the key of the request from the predecessor and the path
+/// ref https://github.com/near/mpc/blob/main/chain-signatures/crypto-shared/src/kdf.rs
+const EPSILON_DERIVATION_PREFIX: &str = "near-mpc-recovery v0.1.0 epsilon derivation:";
+
+/// Chain Signatures request or response. The MPC contract keeps the pending
+/// requests under borsh-encoded keys that don't carry the path nor the key version,
+/// so we read them from the `sign` and `respond` calls to the contract
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ChainSignatureEvent {
+    /// The same for the `sign` and the `respond` of one request, so the
+    /// latency can be measured: the hash of the epsilon and the payload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_version: Option<u64>,
+    pub block_height: BlockHeight,
+}
+
+/// Returns the Chain Signatures events of the executed receipt
+/// to the MPC contract along with the event type for each of them
+pub(crate) fn chain_signature_events(
+    outcome: &IndexerExecutionOutcomeWithReceipt,
+    block_height: BlockHeight,
+) -> Vec<(&'static str, ChainSignatureEvent)> {
+    let actions = match &outcome.receipt.receipt {
+        ReceiptEnumView::Action { actions, .. } => actions,
+        ReceiptEnumView::Data { .. } => return vec![],
+    };
+    actions
+        .iter()
+        .filter_map(|action| match action {
+            ActionView::FunctionCall {
+                method_name, args, ..
+            } => {
+                let args: serde_json::Value =
+                    serde_json::from_slice(&base64::decode(args).ok()?).ok()?;
+                match method_name.as_str() {
+                    "sign" => Some((
+                        "chain_sig_request",
+                        sign_event(&outcome.receipt.predecessor_id, &args, block_height),
+                    )),
+                    "respond" => Some(("chain_sig_response", respond_event(&args, block_height))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// `sign({"request": {"payload", "path", "key_version"}})`, the epsilon isn't
+/// passed, the contract derives it from the caller and the path
+fn sign_event(
+    predecessor_id: &AccountId,
+    args: &serde_json::Value,
+    block_height: BlockHeight,
+) -> ChainSignatureEvent {
+    let request = &args["request"];
+    let payload = scalar_bytes(&request["payload"]);
+    let path = request["path"].as_str().map(str::to_string);
+    let epsilon = path
+        .as_deref()
+        .map(|path| derive_epsilon(predecessor_id, path));
+    ChainSignatureEvent {
+        request_id: request_id(epsilon.as_deref(), payload.as_deref()),
+        payload_hash: payload.map(hex::encode),
+        path,
+        key_version: request["key_version"].as_u64(),
+        block_height,
+    }
+}
+
+/// `respond({"request": {"epsilon", "payload_hash"}, "response"})`
+fn respond_event(args: &serde_json::Value, block_height: BlockHeight) -> ChainSignatureEvent {
+    let request = &args["request"];
+    let payload = scalar_bytes(&request["payload_hash"]);
+    let epsilon = scalar_bytes(&request["epsilon"]);
+    ChainSignatureEvent {
+        request_id: request_id(epsilon.as_deref(), payload.as_deref()),
+        payload_hash: payload.map(hex::encode),
+        path: None,
+        key_version: None,
+        block_height,
+    }
+}
+
+/// The epsilon is reduced modulo the curve order by the contract, which only
+/// changes the hashes over the order, one in 2^128
+fn derive_epsilon(predecessor_id: &AccountId, path: &str) -> Vec<u8> {
+    let derivation_path = format!("{}{},{}", EPSILON_DERIVATION_PREFIX, predecessor_id, path);
+    Sha3_256::digest(derivation_path.as_bytes()).to_vec()
+}
+
+fn request_id(epsilon: Option<&[u8]>, payload: Option<&[u8]>) -> Option<String> {
+    let mut hasher = Sha3_256::new();
+    hasher.update(epsilon?);
+    hasher.update(payload?);
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// The contract versions pass the 32-byte values either as byte arrays,
+/// as hex strings or as the serialized scalars `{"scalar": "<hex>"}`
+fn scalar_bytes(value: &serde_json::Value) -> Option<Vec<u8>> {
+    match value {
+        serde_json::Value::String(hex_value) => {
+            hex::decode(hex_value.strip_prefix("0x").unwrap_or(hex_value)).ok()
+        }
+        serde_json::Value::Array(bytes) => bytes
+            .iter()
+            .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+            .collect(),
+        serde_json::Value::Object(scalar) => scalar_bytes(scalar.get("scalar")?),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn predecessor_id() -> AccountId {
+        "alice.near".parse().unwrap()
+    }
+
+    #[test]
+    fn response_has_the_request_id_of_its_request() {
+        let payload = [7u8; 32];
+        let request = sign_event(
+            &predecessor_id(),
+            &json!({"request": {"payload": payload, "path": "ethereum-1", "key_version": 0}}),
+            100,
+        );
+        let epsilon = hex::encode(derive_epsilon(&predecessor_id(), "ethereum-1"));
+        let response = respond_event(
+            &json!({
+                "request": {
+                    "epsilon": {"scalar": epsilon.to_uppercase()},
+                    "payload_hash": {"scalar": hex::encode(payload)},
+                },
+                "response": {},
+            }),
+            103,
+        );
+        assert!(request.request_id.is_some());
+        assert_eq!(request.request_id, response.request_id);
+        assert_eq!(request.payload_hash, response.payload_hash);
+        assert_eq!(request.path.as_deref(), Some("ethereum-1"));
+        assert_eq!(request.key_version, Some(0));
+        assert_eq!((request.block_height, response.block_height), (100, 103));
+    }
+
+    #[test]
+    fn other_paths_and_callers_are_other_requests() {
+        let payload = [7u8; 32];
+        let args = |path: &str| json!({"request": {"payload": payload, "path": path}});
+        let request = sign_event(&predecessor_id(), &args("ethereum-1"), 100);
+        let other_path = sign_event(&predecessor_id(), &args("ethereum-2"), 100);
+        let other_caller = sign_event(&"bob.near".parse().unwrap(), &args("ethereum-1"), 100);
+        assert_ne!(request.request_id, other_path.request_id);
+        assert_ne!(request.request_id, other_caller.request_id);
+    }
+
+    #[test]
+    fn has_no_request_id_without_payload() {
+        let request = sign_event(&predecessor_id(), &json!({"request": {"path": "p"}}), 100);
+        assert_eq!(request.request_id, None);
+        assert_eq!(scalar_bytes(&json!([1, 256])), None);
+        assert_eq!(scalar_bytes(&json!("0x0102")), Some(vec![1, 2]));
+    }
+}
@@ -0,0 +1,867 @@
+use aws_types::credentials::SharedCredentialsProvider;
+use clap::{CommandFactory, Parser};
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+use near_lake_framework::{LakeConfig, LakeConfigBuilder};
+
+/// NEAR Lake buckets for both mainnet and testnet live in this region
+pub(crate) const LAKE_S3_REGION: &str = "eu-central-1";
+
+#[derive(Parser)]
+#[clap(
+    author = "Near Inc. <hello@nearprotocol.com",
+    subcommand_negates_reqs = true
+)]
+pub(crate) struct Opts {
+    #[clap(long, short)]
+    pub accounts: Vec<AccountId>,
+    #[clap(
+        long,
+        short,
+        required_unless_present_any = &[
+            "start-from-genesis",
+            "start-from-epoch",
+            "time-travel",
+            "block-range-list",
+        ]
+    )]
+    pub block_height: Option<u64>,
+    /// Process only the blocks from the file, one height (`HEIGHT`)
+    /// or range (`START-END`) per line, instead of streaming from a height
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = &["block-height", "start-from-genesis", "start-from-epoch", "time-travel"]
+    )]
+    pub block_range_list: Option<std::path::PathBuf>,
+    /// Start from the genesis block of the chain
+    #[clap(
+        long,
+        conflicts_with_all = &["block-height", "start-from-epoch", "time-travel"]
+    )]
+    pub start_from_genesis: bool,
+    /// Start from the first block of the epoch N, looked up via the RPC
+    #[clap(long, value_name = "N", conflicts_with_all = &["block-height", "time-travel"])]
+    pub start_from_epoch: Option<u64>,
+    /// Start from the first block produced at or after the time,
+    /// e.g. `2024-01-15T00:00:00Z`, looked up via the RPC
+    #[clap(
+        long,
+        value_name = "RFC3339",
+        parse(try_from_str = chrono::DateTime::parse_from_rfc3339),
+        conflicts_with = "block-height"
+    )]
+    pub time_travel: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// NEAR RPC to look the blocks up with, an archival node of the chain by default
+    #[clap(long)]
+    pub rpc_url: Option<String>,
+    /// Print only the fields that have changed since the previous
+    /// `AccountUpdate` of the same account
+    #[clap(long)]
+    pub delta_encoding: bool,
+    /// Add the hash of the transaction the receipt-caused changes originate from.
+    /// Only the receipts produced in the same block can be resolved
+    #[clap(long)]
+    pub resolve_tx_hash: bool,
+    /// Add the shard of the change and the number of shards in the block to the printed
+    /// changes, and print when the number of shards changes
+    #[clap(long)]
+    pub sharding_aware: bool,
+    /// Add the protocol version the block was produced with to the printed changes
+    #[clap(long)]
+    pub include_protocol_version: bool,
+    /// Skip the blocks produced with a protocol version other than the given one
+    #[clap(long, value_name = "VERSION")]
+    pub protocol_version_filter: Option<u32>,
+    /// Print one record per account per block with all the matched changes
+    /// of the account in the `changes` array. It replaces the per-change output,
+    /// `--near-wallet-integration` and `--delta-encoding` included
+    #[clap(long)]
+    pub summarize_by_account: bool,
+    /// Print the matched changes as JSON transaction history items
+    /// compatible with the ones NEAR Wallet displays
+    #[clap(long)]
+    pub near_wallet_integration: bool,
+    /// Print only these fields of the matched changes, dot-separated paths
+    /// like `change.amount` (comma-separated or passed multiple times)
+    #[clap(long, use_value_delimiter = true)]
+    pub include_fields: Vec<String>,
+    /// Don't print these fields of the matched changes, dot-separated paths
+    /// like `change.code_hash` (comma-separated or passed multiple times)
+    #[clap(long, use_value_delimiter = true)]
+    pub exclude_fields: Vec<String>,
+    /// AWS profile to read the credentials from instead of the default one
+    #[clap(long)]
+    pub aws_profile: Option<String>,
+    /// AWS access key id, overrides the credentials from the environment
+    #[clap(long, requires = "aws-secret-access-key")]
+    pub aws_access_key_id: Option<String>,
+    /// AWS secret access key, overrides the credentials from the environment
+    #[clap(long, requires = "aws-access-key-id")]
+    pub aws_secret_access_key: Option<String>,
+    /// ARN of the AWS role to assume using the credentials above
+    #[clap(long)]
+    pub aws_role_arn: Option<String>,
+    /// Check the genesis block of the bucket we read from
+    /// belongs to the chain we expect before starting
+    #[clap(long)]
+    pub verify_genesis: bool,
+    /// Keep the received blocks in the directory and replay them from there
+    /// instead of fetching them from S3 again after a restart
+    #[clap(long, value_name = "DIR")]
+    pub s3_object_cache: Option<std::path::PathBuf>,
+    /// Remove the oldest cached blocks when the cache grows over N megabytes
+    #[clap(long, value_name = "N", requires = "s3-object-cache")]
+    pub s3_cache_max_size_mb: Option<u64>,
+    /// Fetch only the blocks listed in the index file of the bucket, then continue
+    /// with the regular stream after the last of them. All the blocks are fetched
+    /// if the bucket has no index file
+    #[clap(long, conflicts_with_all = &["block-range-list", "s3-object-cache"])]
+    pub use_index_file: bool,
+    /// Key of the index file in the bucket (used by `--use-index-file`)
+    #[clap(
+        long,
+        value_name = "KEY",
+        default_value = "index/blocks_with_changes.txt"
+    )]
+    pub index_file_key: String,
+    /// Check the start block can be read from the bucket
+    /// and the alert webhook accepts requests before starting
+    #[clap(long)]
+    pub startup_validation: bool,
+    /// Compare the MD5 of the objects the watcher fetches itself
+    /// (`--block-range-list`, `--use-index-file`, `--startup-validation`, `--verify-genesis`)
+    /// with their ETags
+    #[clap(long)]
+    pub verify_checksums: bool,
+    /// Up to MAX random milliseconds added to the delay before fetching a block again
+    /// (`--block-range-list` and `--use-index-file`)
+    #[clap(long, value_name = "MAX", default_value = "500")]
+    pub retry_jitter_ms: u64,
+    /// The longest delay before fetching a block again, the delay doubles
+    /// with every attempt up to it
+    #[clap(long, value_name = "MAX", default_value = "30000")]
+    pub retry_max_backoff_ms: u64,
+    /// Genesis block hash to expect, by default it is known from the chain id
+    /// (implies `--verify-genesis`)
+    #[clap(long)]
+    pub expected_genesis_hash: Option<String>,
+    /// Add NEAR Explorer link of the transaction (or the block) to the output
+    #[clap(long)]
+    pub near_tx_explorer_link: bool,
+    /// Custom explorer URL to build the links with (implies `--near-tx-explorer-link`)
+    #[clap(long)]
+    pub explorer_base_url: Option<String>,
+    /// Look up the transaction or the receipt that caused the change
+    /// and print its details along with the change
+    #[clap(long)]
+    pub print_cause: bool,
+    /// Print the report of the most often changed accounts every N blocks
+    #[clap(long)]
+    pub change_frequency_report: Option<u64>,
+    /// Print the number of changes and changed accounts per account namespace
+    /// (`myapp.near` for `alice.myapp.near`), with `--ecosystem-stats` for the whole chain
+    #[clap(long)]
+    pub namespace_stats: bool,
+    /// How often to print the `--namespace-stats`
+    #[clap(long, value_name = "N", default_value = "100")]
+    pub namespace_stats_interval_blocks: u64,
+    /// Print the list of the watched accounts every N blocks
+    #[clap(long, value_name = "N")]
+    pub accounts_report_every_n_blocks: Option<u64>,
+    /// Write the list of the watched accounts to the file instead of stderr
+    #[clap(long, requires = "accounts-report-every-n-blocks")]
+    pub accounts_report_file: Option<std::path::PathBuf>,
+    /// Keep counting the changes from the start instead of resetting
+    /// the counters after each report
+    #[clap(long)]
+    pub cumulative_frequency: bool,
+    /// Account to exclude from the output even if it is watched,
+    /// either an exact account name or a regex (can be passed multiple times)
+    #[clap(long)]
+    pub denylist_account: Vec<String>,
+    /// File with the denylist patterns, one per line
+    #[clap(long)]
+    pub denylist_file: Option<std::path::PathBuf>,
+    /// Decode the EVM event logs of the Aurora transactions
+    /// (requires `aurora` to be watched)
+    #[clap(long)]
+    pub aurora_evm_events: bool,
+    /// Contract ABI JSON file to decode the known Aurora EVM events with
+    #[clap(long)]
+    pub aurora_abi_file: Option<std::path::PathBuf>,
+    /// Print the NEP-141 tokens locked in and unlocked from the Aurora ERC-20 bridge
+    /// (requires `aurora` in the watched accounts)
+    #[clap(long)]
+    pub aurora_bridge_tracking: bool,
+    /// Decode the EVM transactions submitted to Aurora and print their sender,
+    /// receiver, value and data (requires `aurora` in the watched accounts)
+    #[clap(long)]
+    pub aurora_tx_tracking: bool,
+    /// Print only the EVM transactions from or to these addresses
+    #[clap(long, requires = "aurora-tx-tracking", use_value_delimiter = true)]
+    pub aurora_watch_evm_address: Vec<String>,
+    /// Print the collateral, borrow and liquidation events
+    /// of the Burrow lending protocol
+    #[clap(long)]
+    pub burrow_tracking: bool,
+    /// Print the swaps in the Ref Finance pools
+    #[clap(long)]
+    pub ref_finance_tracking: bool,
+    /// Print the delegator actions on the watched staking pools
+    #[clap(long)]
+    pub staking_pool_tracking: bool,
+    /// Print the price feed updates of the watched price oracles
+    #[clap(long)]
+    pub oracle_tracking: bool,
+    /// Print an alert when a price changes by more than the given percentage
+    /// since its previous update
+    #[clap(long, requires = "oracle-tracking")]
+    pub oracle_price_deviation_alert: Option<f64>,
+    /// Print the proposals added, voted on and finalized in the AstroDAO DAOs,
+    /// all the `*.sputnik-dao.near` accounts are tracked
+    #[clap(long)]
+    pub dao_tracking: bool,
+    /// File with more DAO accounts to track, one per line (used by `--dao-tracking`)
+    #[clap(long, requires = "dao-tracking")]
+    pub dao_accounts_file: Option<std::path::PathBuf>,
+    /// Print how long it took to handle each shard of the block
+    /// and how many changes and receipts it had
+    #[clap(long)]
+    pub shard_stats: bool,
+    /// Also print the per-shard totals every N blocks (requires `--shard-stats`)
+    #[clap(long, requires = "shard-stats")]
+    pub shard_stats_summary_interval_blocks: Option<u64>,
+    /// Don't stop on the block we fail to handle, log the error and continue
+    /// with the next block
+    #[clap(long)]
+    pub safe_mode: bool,
+    /// File to append the failed blocks to (requires `--safe-mode`)
+    #[clap(long, requires = "safe-mode")]
+    pub safe_mode_error_log: Option<std::path::PathBuf>,
+    /// Stop reading the stream and flush the buffers when the resident memory
+    /// of the watcher grows over N megabytes (Linux only)
+    #[clap(long, value_name = "N")]
+    pub max_memory_mb: Option<u64>,
+    /// Write to the account history files and DuckDB from a separate task,
+    /// so a slow sink doesn't hold back handling the blocks
+    #[clap(long)]
+    pub async_writes: bool,
+    /// Number of the writes the queue holds (used by `--async-writes`)
+    #[clap(long, value_name = "N", default_value = "1000")]
+    pub async_write_queue_depth: usize,
+    /// What to do when the queue is full: drop the write, wait until there is room
+    /// or stop the watcher (used by `--async-writes`)
+    #[clap(long, arg_enum, default_value = "block")]
+    pub async_write_error_mode: AsyncWriteErrorMode,
+    /// Directory to write the history of each watched account to,
+    /// one `<account_id>.ndjson` file per account
+    #[clap(long)]
+    pub near_account_history: Option<std::path::PathBuf>,
+    /// Rotate the account history file when it grows over N megabytes
+    /// (requires `--near-account-history`)
+    #[clap(long, requires = "near-account-history")]
+    pub account_history_max_size_mb: Option<u64>,
+    /// Gzip the account history files into `<account_id>.ndjson.gz`, every block
+    /// is a gzip member of its own so the file can be read while it's written
+    /// (requires `--near-account-history`)
+    #[clap(long, requires = "near-account-history")]
+    pub output_gzip_compress: bool,
+    /// Gzip compression level from 1 (fastest) to 9 (smallest)
+    /// (used by `--output-gzip-compress`)
+    #[clap(
+        long,
+        value_name = "1-9",
+        default_value = "6",
+        parse(try_from_str = parse_gzip_level)
+    )]
+    pub gzip_level: u32,
+    /// Check every block follows the previous one we have received
+    /// and report the blocks that arrive out of order or after a gap
+    #[clap(long)]
+    pub verify_sequence: bool,
+    /// Stop the watcher on the first out of order block (requires `--verify-sequence`)
+    #[clap(long, requires = "verify-sequence")]
+    pub verify_sequence_fatal: bool,
+    /// Print when the unvested balance of the watched `*.lockup.near` accounts
+    /// decreases, decoded from the lockup contract state. The lockups with
+    /// a private vesting schedule (only its hash is stored) are skipped
+    #[clap(long)]
+    pub lockup_tracking: bool,
+    /// Print the IBC channel and packet events of the NEAR IBC contract
+    #[clap(long, requires = "ibc-contract-account")]
+    pub near_ibc_tracking: bool,
+    /// NEAR IBC contract account (used by `--near-ibc-tracking`)
+    #[clap(long)]
+    pub ibc_contract_account: Option<AccountId>,
+    /// Print the signature requests and responses of the Chain Signatures MPC contract
+    #[clap(long)]
+    pub chain_sig_tracking: bool,
+    /// Print the NEP-141 `ft_transfer` events the watched accounts take part in
+    #[clap(long)]
+    pub watch_ft_transfers: bool,
+    /// JSON file mapping the token contracts to their symbols and decimals,
+    /// reloaded on SIGHUP (used by `--watch-ft-transfers`)
+    #[clap(long)]
+    pub ft_token_list: Option<std::path::PathBuf>,
+    /// Print an alert when a full access key is added to a watched account
+    #[clap(long)]
+    pub keystore_watch: bool,
+    /// Public keys that are expected to be added, their alerts are not critical
+    /// and are not sent to the webhook (used by `--keystore-watch`)
+    #[clap(long, use_value_delimiter = true, requires = "keystore-watch")]
+    pub allowed_full_access_keys: Vec<String>,
+    /// URL to POST the critical alerts to as JSON
+    #[clap(long)]
+    pub alert_webhook: Option<String>,
+    /// Slack incoming webhook URL to post the matched changes and the critical alerts to
+    #[clap(long, value_name = "URL")]
+    pub alert_slack_webhook: Option<String>,
+    /// Slack user ID to mention in the critical alerts
+    #[clap(long, value_name = "USER_ID", requires = "alert-slack-webhook")]
+    pub slack_mention_user: Option<String>,
+    /// Discord webhook URL to post the matched changes and the critical alerts to
+    #[clap(long, value_name = "URL")]
+    pub alert_discord_webhook: Option<String>,
+    /// Discord role ID to ping in the critical alerts
+    #[clap(long, value_name = "ROLE_ID", requires = "alert-discord-webhook")]
+    pub discord_role_mention: Option<String>,
+    /// Color of the critical alert embeds, e.g. `E01E5A`
+    #[clap(
+        long,
+        value_name = "HEX",
+        default_value = "E01E5A",
+        parse(try_from_str = parse_hex_color),
+        requires = "alert-discord-webhook"
+    )]
+    pub discord_embed_color_critical: u32,
+    /// Types of the watched changes to treat as critical alerts,
+    /// e.g. `access_key_update,contract_code_update`
+    #[clap(long, use_value_delimiter = true)]
+    pub critical_change_types: Vec<String>,
+    /// Order of the matched changes within a block: `natural` as they are found,
+    /// `shard` by the shard id, `type` by the `--event-type-priority`
+    /// among the changes of the same account
+    #[clap(long, arg_enum, default_value = "natural")]
+    pub event_ordering: EventOrdering,
+    /// Change types in the order `--event-ordering type` prints them
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        default_values = &[
+            "account_deletion",
+            "access_key_deletion",
+            "data_deletion",
+            "contract_code_deletion",
+        ]
+    )]
+    pub event_type_priority: Vec<String>,
+    /// PagerDuty Events API v2 routing key to trigger incidents for the critical alerts with
+    #[clap(long, value_name = "ROUTING_KEY")]
+    pub alert_pagerduty: Option<String>,
+    /// Resolve the PagerDuty incident after N blocks without critical alerts
+    /// of the same account and change type
+    #[clap(
+        long,
+        default_value = "100",
+        value_name = "N",
+        requires = "alert-pagerduty"
+    )]
+    pub pagerduty_resolve_after_blocks: u64,
+    /// Don't repeat the webhook, Slack and Discord alerts of the same account and
+    /// change type within the given number of seconds. The changes are still printed and written to
+    /// the sinks, and PagerDuty merges the repeated alerts into the open incident itself
+    #[clap(long, value_name = "SECONDS")]
+    pub alert_silence_window: Option<u64>,
+    /// Print an alert when a block comes more than the given number of milliseconds
+    /// after the previous one, or the last 100 blocks took twice as long on average
+    #[clap(long, value_name = "MS")]
+    pub block_time_alert: Option<u64>,
+    /// NEAR ABI file (as generated by `cargo-near`) to check the arguments
+    /// of the function calls to the watched accounts against
+    #[clap(long, value_name = "ABI_FILE")]
+    pub abi_check: Option<std::path::PathBuf>,
+    /// Print an alert when the receipts executed in a block contain the given
+    /// sequence of function calls, a JSON array of `{"receiver": .., "method": ..}`
+    #[clap(long, value_name = "JSON")]
+    pub tx_pattern: Option<String>,
+    /// Measure how many blocks pass between a receipt to a watched account
+    /// is created and executed
+    #[clap(long)]
+    pub receipt_delay_tracking: bool,
+    /// Print an alert when a receipt to a watched account is executed
+    /// more than N blocks after it has been created
+    #[clap(long, default_value = "5", value_name = "N")]
+    pub max_receipt_delay_blocks: u64,
+    /// Print when a watched account has its first `AccountUpdate` we have seen
+    #[clap(long)]
+    pub account_birth_tracking: bool,
+    /// File to keep the seen accounts in between the runs
+    /// (used by `--account-birth-tracking`)
+    #[clap(long, requires = "account-birth-tracking")]
+    pub account_birth_cache_file: Option<std::path::PathBuf>,
+    /// Print the NEAR sent to the watched accounts with a `Transfer`
+    /// or attached to a function call
+    #[clap(long)]
+    pub near_transfer_detection: bool,
+    /// Print an alert when a contract bigger than the given number of bytes
+    /// is deployed to a watched account
+    #[clap(long)]
+    pub contract_size_alert_bytes: Option<u64>,
+    /// Print an alert when the storage usage of a watched account
+    /// reaches the given number of bytes
+    #[clap(long)]
+    pub storage_alert_bytes: Option<u64>,
+    /// How far (in percent of `--storage-alert-bytes`) the storage usage
+    /// has to drop before the account can alert again
+    #[clap(long, default_value = "10", requires = "storage-alert-bytes")]
+    pub storage_alert_hysteresis_pct: u8,
+    /// Add the pre-signed S3 URLs of the block and shard objects to the matched
+    /// changes, the URLs expire after the given number of seconds (one week at most)
+    #[clap(long, value_name = "EXPIRY_SECONDS")]
+    pub presign_matched_blocks: Option<u64>,
+    /// WebAssembly module to filter and transform the matched changes with
+    /// (needs the `wasm-plugin` cargo feature)
+    #[clap(long)]
+    pub wasm_plugin: Option<std::path::PathBuf>,
+    /// Fuel the WASM plugin gets for each change, the change fails
+    /// once the plugin has used it up
+    #[clap(long, default_value = "10000000", requires = "wasm-plugin")]
+    pub wasm_fuel_limit: u64,
+    /// DuckDB database file to insert the matched state changes into
+    /// (needs the `duckdb` cargo feature)
+    #[clap(long)]
+    pub duckdb_path: Option<std::path::PathBuf>,
+    /// Keep the DuckDB database in memory, it's gone once the watcher exits
+    #[clap(long, conflicts_with = "duckdb-path")]
+    pub duckdb_in_memory: bool,
+    /// SQL query to run against the DuckDB database when the watcher exits,
+    /// the result is printed to stdout
+    #[clap(long)]
+    pub duckdb_query: Option<String>,
+    /// Print when the FastAuth passkeys are added to or removed from any account
+    #[clap(long, requires = "fastauth-authenticator")]
+    pub fastauth_tracking: bool,
+    /// FastAuth authenticator contract the passkeys are allowed to call
+    /// (used by `--fastauth-tracking`)
+    #[clap(long)]
+    pub fastauth_authenticator: Option<AccountId>,
+    /// Print how long each step of handling the block took and write the
+    /// `tracing.folded` file for `scripts/flamegraph.sh`
+    /// (needs the `developer-mode` cargo feature)
+    #[clap(long)]
+    pub developer_mode: bool,
+    /// Print every state change of the shard in full regardless of the watched accounts,
+    /// along with the regular output
+    #[clap(long, value_name = "SHARD_ID", requires = "allow-debug-flags")]
+    pub debug_shard: Option<u64>,
+    /// Stop printing the `--debug-shard` changes after N blocks
+    #[clap(long, value_name = "N", requires = "debug-shard")]
+    pub debug_shard_max_blocks: Option<u64>,
+    /// Confirm the debug flags that flood the output aren't passed by mistake
+    #[clap(long)]
+    pub allow_debug_flags: bool,
+    /// Watch all the accounts regardless of `--accounts` and print the chain-wide
+    /// numbers of each block
+    #[clap(long)]
+    pub ecosystem_stats: bool,
+    /// Split the accounts between TOTAL watcher instances and handle only
+    /// the ones assigned to the INDEX, e.g. `0/4`
+    #[clap(long, value_name = "INDEX/TOTAL")]
+    pub consistent_hash_shard: Option<crate::consistent_hash::HashShard>,
+    /// Print at most N changes per block with `--ecosystem-stats`,
+    /// the rest are only counted
+    #[clap(long, value_name = "N", requires = "ecosystem-stats")]
+    pub ecosystem_stats_max_rate_limit: Option<u64>,
+    /// Print the NEAR distributed by the testnet faucet regardless of the watched accounts
+    #[clap(long)]
+    pub faucet_tracking: bool,
+    /// Faucet account to track instead of the known testnet one
+    #[clap(long, requires = "faucet-tracking")]
+    pub faucet_account: Option<AccountId>,
+    /// Print the validators added, removed or restaked at each epoch boundary,
+    /// the validator sets are fetched from the RPC
+    #[clap(long)]
+    pub validator_set_tracking: bool,
+    /// Watch the changes of all the active validators too
+    #[clap(long, requires = "validator-set-tracking")]
+    pub watch_validators: bool,
+    /// Print the widgets deployed to Near.Social. The paths of the Near.Social data
+    /// are rebuilt from the contract storage, so only the widgets added since
+    /// the watcher has started are known
+    #[clap(long)]
+    pub social_widget_tracking: bool,
+    /// Print only the widgets of the given author (can be passed multiple times)
+    #[clap(long, requires = "social-widget-tracking")]
+    pub track_widget_author: Vec<AccountId>,
+    /// Print when the watched accounts gain or lose followers on Near.Social,
+    /// for the follows added since the watcher has started
+    #[clap(long)]
+    pub near_social_follow: bool,
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug)]
+pub(crate) enum EventOrdering {
+    Type,
+    Shard,
+    Natural,
+}
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug)]
+pub(crate) enum AsyncWriteErrorMode {
+    Drop,
+    Block,
+    Abort,
+}
+
+#[derive(clap::Subcommand)]
+pub(crate) enum Command {
+    /// The chains the watcher runs on
+    #[clap(flatten)]
+    Chain(ChainId),
+    /// Print the completions script for the given shell
+    GenerateCompletions {
+        #[clap(arg_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Fetch the block from the NEAR Lake bucket and print all its state changes
+    ///
+    /// It reads a single block without the streamer, the watcher's options are ignored
+    /// and the AWS credentials are read from the environment
+    DebugBlock {
+        height: BlockHeight,
+        #[clap(subcommand)]
+        chain_id: ChainId,
+    },
+}
+
+#[derive(Parser)]
+pub(crate) enum ChainId {
+    Mainnet,
+    Testnet,
+    /// Custom NEAR Lake deployment, e.g. your own bucket or a local MinIO
+    Custom {
+        /// S3 bucket with the blocks
+        #[clap(long)]
+        bucket: String,
+        /// Region of the bucket
+        #[clap(long, default_value = LAKE_S3_REGION)]
+        region: String,
+        /// S3-compatible endpoint to use instead of AWS, e.g. `http://localhost:9000`
+        #[clap(long)]
+        endpoint: Option<http::Uri>,
+    },
+}
+
+impl ChainId {
+    /// Genesis block height and hash of the chain,
+    /// we don't know them for the custom deployments
+    pub(crate) fn genesis(&self) -> Option<(BlockHeight, CryptoHash)> {
+        let (height, hash) = match self {
+            ChainId::Mainnet => (9820210, "EPnLgE7iEq9s7yTkos96M3cWymH5avBAPm3qx3NXqR8H"),
+            ChainId::Testnet => (42376888, "FWJ9kR6KFWoyMoNjpLXXGHeuiy7tEY6GmoFeCA5yuc6b"),
+            ChainId::Custom { .. } => return None,
+        };
+        Some((height, hash.parse().expect("Invalid genesis hash")))
+    }
+
+    /// The Chain Signatures MPC contract account of the chain
+    pub(crate) fn chain_signatures_account(&self) -> Option<AccountId> {
+        let account_id = match self {
+            ChainId::Mainnet => "v1.signer",
+            ChainId::Testnet => "v1.signer-prod.testnet",
+            ChainId::Custom { .. } => return None,
+        };
+        Some(
+            account_id
+                .parse()
+                .expect("Invalid Chain Signatures account"),
+        )
+    }
+
+    /// The Near.Social contract account of the chain
+    pub(crate) fn social_db_account(&self) -> Option<AccountId> {
+        let account_id = match self {
+            ChainId::Mainnet => "social.near",
+            ChainId::Testnet => "v1.social08.testnet",
+            ChainId::Custom { .. } => return None,
+        };
+        Some(account_id.parse().expect("Invalid Near.Social account"))
+    }
+
+    /// The NEAR faucet of the chain, only testnet has one
+    pub(crate) fn faucet_account(&self) -> Option<AccountId> {
+        match self {
+            ChainId::Testnet => Some(
+                "faucet.noncepool.testnet"
+                    .parse()
+                    .expect("Invalid faucet account"),
+            ),
+            ChainId::Mainnet | ChainId::Custom { .. } => None,
+        }
+    }
+
+    /// Archival RPC of the chain, it can answer about the old blocks too
+    pub(crate) fn rpc_url(&self) -> Option<&'static str> {
+        match self {
+            ChainId::Mainnet => Some("https://archival-rpc.mainnet.near.org"),
+            ChainId::Testnet => Some("https://archival-rpc.testnet.near.org"),
+            ChainId::Custom { .. } => None,
+        }
+    }
+
+    /// The NEAR Lake bucket with the blocks
+    pub(crate) fn s3_bucket_name(&self) -> &str {
+        match self {
+            ChainId::Mainnet => "near-lake-data-mainnet",
+            ChainId::Testnet => "near-lake-data-testnet",
+            ChainId::Custom { bucket, .. } => bucket,
+        }
+    }
+
+    /// Region of the NEAR Lake bucket
+    pub(crate) fn s3_region_name(&self) -> &str {
+        match self {
+            ChainId::Mainnet | ChainId::Testnet => LAKE_S3_REGION,
+            ChainId::Custom { region, .. } => region,
+        }
+    }
+
+    /// S3-compatible endpoint of the custom deployment
+    pub(crate) fn s3_endpoint(&self) -> Option<&http::Uri> {
+        match self {
+            ChainId::Custom { endpoint, .. } => endpoint.as_ref(),
+            ChainId::Mainnet | ChainId::Testnet => None,
+        }
+    }
+}
+
+impl Opts {
+    /// Parses the command-line args, the required options are
+    /// checked only when the watcher runs, not for the other subcommands
+    pub(crate) fn parse_args() -> Self {
+        Self::try_parse_args_from(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    pub(crate) fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let args: Vec<std::ffi::OsString> = args.into_iter().map(Into::into).collect();
+        let opts = Self::try_parse_from(&args)?;
+        if let Command::Chain(_) = opts.command {
+            Self::command()
+                .subcommand_negates_reqs(false)
+                .try_get_matches_from(&args)?;
+        }
+        Ok(opts)
+    }
+
+    /// The chain the watcher runs on
+    pub(crate) fn chain_id(&self) -> &ChainId {
+        match &self.command {
+            Command::Chain(chain_id) | Command::DebugBlock { chain_id, .. } => chain_id,
+            Command::GenerateCompletions { .. } => {
+                unreachable!("The completions are generated before the watcher starts")
+            }
+        }
+    }
+
+    /// Builds the `LakeConfig` for the NEAR Lake Framework
+    /// based on the passed options
+    pub(crate) async fn to_lake_config(&self, start_block_height: BlockHeight) -> LakeConfig {
+        let mut config = LakeConfigBuilder::default().start_block_height(start_block_height);
+
+        match self.chain_id() {
+            ChainId::Mainnet => config = config.mainnet(),
+            ChainId::Testnet => config = config.testnet(),
+            ChainId::Custom { bucket, region, .. } => {
+                config = config.s3_bucket_name(bucket).s3_region_name(region)
+            }
+        };
+
+        if let Some(s3_config) = self.s3_config().await {
+            config = config.s3_config(s3_config);
+        }
+
+        config.build().expect("Failed to build LakeConfig")
+    }
+
+    /// Compiles all the denylist patterns from the command-line
+    /// and the denylist file into a single `RegexSet`
+    pub(crate) fn denylist(&self) -> anyhow::Result<regex::RegexSet> {
+        let mut patterns = self.denylist_account.clone();
+        if let Some(denylist_file) = &self.denylist_file {
+            let content = std::fs::read_to_string(denylist_file).map_err(|err| {
+                anyhow::anyhow!("Failed to read {}: {}", denylist_file.display(), err)
+            })?;
+            patterns.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(|pattern| {
+                // Exact account names are escaped so the dots in them match only the dots
+                if pattern.parse::<AccountId>().is_ok() {
+                    format!("^{}$", regex::escape(&pattern))
+                } else {
+                    format!("^(?:{})$", pattern)
+                }
+            })
+            .collect();
+
+        Ok(regex::RegexSet::new(patterns)?)
+    }
+
+    /// Reads the DAO accounts from the `--dao-accounts-file`, one per line
+    pub(crate) fn dao_accounts(&self) -> anyhow::Result<std::collections::HashSet<AccountId>> {
+        let dao_accounts_file = match &self.dao_accounts_file {
+            Some(dao_accounts_file) => dao_accounts_file,
+            None => return Ok(std::collections::HashSet::new()),
+        };
+        let content = std::fs::read_to_string(dao_accounts_file).map_err(|err| {
+            anyhow::anyhow!("Failed to read {}: {}", dao_accounts_file.display(), err)
+        })?;
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.parse()
+                    .map_err(|err| anyhow::anyhow!("Invalid DAO account {}: {}", line, err))
+            })
+            .collect()
+    }
+
+    /// The `--rpc-url` or the default RPC of the chain
+    pub(crate) fn rpc_url(&self) -> anyhow::Result<&str> {
+        match &self.rpc_url {
+            Some(rpc_url) => Ok(rpc_url),
+            None => self.chain_id().rpc_url().ok_or_else(|| {
+                anyhow::anyhow!("--rpc-url is required for a custom NEAR Lake deployment")
+            }),
+        }
+    }
+
+    /// Loads the ABI to decode the Aurora EVM events with
+    pub(crate) fn aurora_abi(&self) -> anyhow::Result<Option<ethabi::Contract>> {
+        match &self.aurora_abi_file {
+            Some(aurora_abi_file) => {
+                let file = std::fs::File::open(aurora_abi_file).map_err(|err| {
+                    anyhow::anyhow!("Failed to open {}: {}", aurora_abi_file.display(), err)
+                })?;
+                Ok(Some(ethabi::Contract::load(file)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the explorer URL to build the links with
+    /// if the links are requested
+    pub(crate) fn explorer_base_url(&self) -> Option<&str> {
+        match &self.explorer_base_url {
+            Some(explorer_base_url) => Some(explorer_base_url.trim_end_matches('/')),
+            None if self.near_tx_explorer_link => match self.chain_id() {
+                ChainId::Mainnet => Some("https://explorer.near.org"),
+                ChainId::Testnet => Some("https://explorer.testnet.near.org"),
+                ChainId::Custom { .. } => None,
+            },
+            None => None,
+        }
+    }
+
+    /// The backoff of the blocks the watcher fetches itself
+    pub(crate) fn retry_policy(&self) -> crate::retry::RetryPolicy {
+        crate::retry::RetryPolicy {
+            base_ms: 200,
+            jitter_ms: self.retry_jitter_ms,
+            max_ms: self.retry_max_backoff_ms,
+            multiplier: 2,
+        }
+    }
+
+    /// Returns the custom `aws_sdk_s3::Config` if any of the AWS credentials
+    /// options or a custom endpoint were passed, otherwise the NEAR Lake Framework
+    /// reads the credentials from the environment on its own
+    pub(crate) async fn s3_config(&self) -> Option<aws_sdk_s3::Config> {
+        if self.aws_profile.is_none()
+            && self.aws_access_key_id.is_none()
+            && self.aws_role_arn.is_none()
+            && self.chain_id().s3_endpoint().is_none()
+        {
+            return None;
+        }
+
+        let region = aws_types::region::Region::new(self.chain_id().s3_region_name().to_string());
+
+        let mut credentials_provider = match (&self.aws_access_key_id, &self.aws_secret_access_key)
+        {
+            (Some(access_key_id), Some(secret_access_key)) => {
+                SharedCredentialsProvider::new(aws_types::Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    None,
+                    None,
+                    "near-lake-accounts-watcher",
+                ))
+            }
+            _ => {
+                let mut default_chain =
+                    aws_config::default_provider::credentials::DefaultCredentialsChain::builder()
+                        .region(region.clone());
+                if let Some(profile) = &self.aws_profile {
+                    default_chain = default_chain.profile_name(profile);
+                }
+                SharedCredentialsProvider::new(default_chain.build().await)
+            }
+        };
+
+        // The credentials we've got so far are used to assume the role
+        if let Some(role_arn) = &self.aws_role_arn {
+            credentials_provider = SharedCredentialsProvider::new(
+                aws_config::sts::AssumeRoleProvider::builder(role_arn)
+                    .session_name("near-lake-accounts-watcher")
+                    .region(region.clone())
+                    .build(credentials_provider),
+            );
+        }
+
+        let aws_config = aws_config::from_env()
+            .region(region)
+            .credentials_provider(credentials_provider)
+            .load()
+            .await;
+
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&aws_config);
+        if let Some(endpoint) = self.chain_id().s3_endpoint() {
+            s3_config =
+                s3_config.endpoint_resolver(aws_sdk_s3::Endpoint::immutable(endpoint.clone()));
+        }
+        Some(s3_config.build())
+    }
+}
+
+fn parse_gzip_level(level: &str) -> Result<u32, String> {
+    match level.parse() {
+        Ok(level @ 1..=9) => Ok(level),
+        _ => Err(format!("{} is not a gzip level from 1 to 9", level)),
+    }
+}
+
+/// Parses `RRGGBB` with an optional `#`
+fn parse_hex_color(color: &str) -> Result<u32, String> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("{} is not an RRGGBB color", color));
+    }
+    u32::from_str_radix(hex, 16).map_err(|err| format!("{} is not an RRGGBB color: {}", color, err))
+}
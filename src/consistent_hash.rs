@@ -0,0 +1,85 @@
+use std::hash::Hasher;
+
+/// Points each instance gets on the ring, more points spread the accounts more evenly
+const VIRTUAL_NODES: usize = 160;
+
+/// `INDEX/TOTAL` of the `--consistent-hash-shard`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HashShard {
+    pub index: usize,
+    pub total: usize,
+}
+
+impl std::str::FromStr for HashShard {
+    type Err = String;
+
+    fn from_str(shard: &str) -> Result<Self, Self::Err> {
+        let (index, total) = shard
+            .split_once('/')
+            .ok_or_else(|| format!("{} is not INDEX/TOTAL", shard))?;
+        let index: usize = index
+            .parse()
+            .map_err(|err| format!("Invalid index {}: {}", index, err))?;
+        let total: usize = total
+            .parse()
+            .map_err(|err| format!("Invalid total {}: {}", total, err))?;
+        if index >= total {
+            return Err(format!(
+                "Index {} has to be less than the total {}",
+                index, total
+            ));
+        }
+        Ok(Self { index, total })
+    }
+}
+
+/// Assigns every account to one of the watcher instances. An account always lands
+/// on the same instance for the same total, and changing the total moves only
+/// about 1/TOTAL of the accounts
+pub(crate) struct HashRing {
+    /// Sorted hashes of the virtual nodes and the instance each of them belongs to
+    points: Vec<(u64, usize)>,
+    index: usize,
+}
+
+impl HashRing {
+    pub(crate) fn new(shard: HashShard) -> Self {
+        let mut points: Vec<(u64, usize)> = (0..shard.total)
+            .flat_map(|instance| {
+                (0..VIRTUAL_NODES)
+                    .map(move |node| (hash(&format!("{}-{}", instance, node)), instance))
+            })
+            .collect();
+        points.sort_unstable();
+        Self {
+            points,
+            index: shard.index,
+        }
+    }
+
+    /// Whether the account belongs to this instance
+    pub(crate) fn is_assigned(&self, account_id: &str) -> bool {
+        let account_hash = hash(account_id);
+        // The first point clockwise from the account, wrapping around the ring
+        let position = self
+            .points
+            .partition_point(|(point_hash, _)| *point_hash < account_hash);
+        let point = self.points.get(position).or_else(|| self.points.first());
+        point.map_or(true, |(_, instance)| *instance == self.index)
+    }
+}
+
+/// FNV-1a, unlike the std hasher it is the same in every build,
+/// so all the instances agree on the assignment. FNV alone barely changes
+/// the high bits for the short similar keys like `0-1` and `0-2`,
+/// so the result goes through the MurmurHash3 finalizer
+fn hash(value: &str) -> u64 {
+    let mut hasher = fnv::FnvHasher::default();
+    hasher.write(value.as_bytes());
+    let mut hash = hasher.finish();
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// The deployed contract is bigger than `--contract-size-alert-bytes`
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ContractSizeAlert {
+    pub account_id: AccountId,
+    pub code_size_bytes: u64,
+    pub threshold_bytes: u64,
+    /// Compared to the previous deployment seen during the run
+    pub code_size_delta_bytes: Option<i64>,
+    pub block_height: BlockHeight,
+}
+
+/// Remembers the code size of each deployment to tell how the next one has changed
+#[derive(Debug)]
+pub(crate) struct ContractSizeTracker {
+    threshold_bytes: u64,
+    code_sizes: HashMap<AccountId, u64>,
+}
+
+impl ContractSizeTracker {
+    pub(crate) fn new(threshold_bytes: u64) -> Self {
+        Self {
+            threshold_bytes,
+            code_sizes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn check(
+        &mut self,
+        account_id: &AccountId,
+        code: &[u8],
+        block_height: BlockHeight,
+    ) -> Option<ContractSizeAlert> {
+        let code_size_bytes = code.len() as u64;
+        let previous_size = self.code_sizes.insert(account_id.clone(), code_size_bytes);
+        if code_size_bytes <= self.threshold_bytes {
+            return None;
+        }
+        Some(ContractSizeAlert {
+            account_id: account_id.clone(),
+            code_size_bytes,
+            threshold_bytes: self.threshold_bytes,
+            code_size_delta_bytes: previous_size
+                .map(|previous_size| code_size_bytes as i64 - previous_size as i64),
+            block_height,
+        })
+    }
+}
@@ -0,0 +1,82 @@
+use near_lake_framework::near_indexer_primitives::types::{BlockHeight, StorageUsage};
+use near_lake_framework::near_indexer_primitives::views::AccountView;
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+/// Holds only the fields of the `AccountView` that have changed
+/// between two consecutive `AccountUpdate` events of the same account.
+/// Fields that haven't changed are `None` and are omitted from the JSON.
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct AccountDelta {
+    // Balances are serialized as strings the same way NEAR does it,
+    // because u128 doesn't fit into JSON number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<CryptoHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_usage: Option<StorageUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_paid_at: Option<BlockHeight>,
+}
+
+impl AccountDelta {
+    /// Compares the previously seen `AccountView` with the new one
+    /// and keeps only the fields that differ
+    pub(crate) fn compute(old: &AccountView, new: &AccountView) -> Self {
+        Self {
+            amount: (old.amount != new.amount).then(|| new.amount.to_string()),
+            locked: (old.locked != new.locked).then(|| new.locked.to_string()),
+            code_hash: (old.code_hash != new.code_hash).then(|| new.code_hash),
+            storage_usage: (old.storage_usage != new.storage_usage).then(|| new.storage_usage),
+            storage_paid_at: (old.storage_paid_at != new.storage_paid_at)
+                .then(|| new.storage_paid_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(amount: u128) -> AccountView {
+        AccountView {
+            amount,
+            locked: 0,
+            code_hash: CryptoHash::default(),
+            storage_usage: 182,
+            storage_paid_at: 0,
+        }
+    }
+
+    #[test]
+    fn unchanged_account_has_empty_delta() {
+        let delta = AccountDelta::compute(&account(100), &account(100));
+        assert_eq!(serde_json::to_value(&delta).unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn keeps_decreased_amount_only() {
+        let delta = AccountDelta::compute(&account(100), &account(40));
+        assert_eq!(
+            serde_json::to_value(&delta).unwrap(),
+            serde_json::json!({"amount": "40"})
+        );
+    }
+
+    #[test]
+    fn keeps_new_code_hash() {
+        let new = AccountView {
+            code_hash: CryptoHash([1; 32]),
+            storage_usage: 1000,
+            ..account(100)
+        };
+        let delta = AccountDelta::compute(&account(100), &new);
+        assert_eq!(delta.code_hash, Some(CryptoHash([1; 32])));
+        assert_eq!(delta.storage_usage, Some(1000));
+        assert_eq!(delta.amount, None);
+        assert_eq!(delta.locked, None);
+        assert_eq!(delta.storage_paid_at, None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Wall-clock time spent in each step of handling a block, for profiling the
+//! watcher itself. Needs the `developer-mode` cargo feature, without it the
+//! timers are empty and compile out.
+
+#[cfg(feature = "developer-mode")]
+use std::cell::Cell;
+#[cfg(feature = "developer-mode")]
+use std::time::{Duration, Instant};
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Step {
+    StreamReceive,
+    ShardIteration,
+    Matching,
+    Serialization,
+    SinkWrite,
+}
+
+#[cfg(feature = "developer-mode")]
+impl Step {
+    const ALL: usize = 5;
+
+    fn name(&self) -> &'static str {
+        match self {
+            Step::StreamReceive => "stream_receive",
+            Step::ShardIteration => "shard_iteration",
+            Step::Matching => "matching",
+            Step::Serialization => "serialization",
+            Step::SinkWrite => "sink_write",
+        }
+    }
+}
+
+/// Durations of the steps of a single block in microseconds
+#[cfg_attr(not(feature = "developer-mode"), allow(dead_code))]
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct DevTimingRecord {
+    pub block_height: BlockHeight,
+    pub stream_receive_us: u128,
+    pub shard_iteration_us: u128,
+    pub matching_us: u128,
+    pub serialization_us: u128,
+    pub sink_write_us: u128,
+}
+
+/// Sums up the time spent in each step until the block is done
+#[derive(Debug, Default)]
+pub(crate) struct DevTiming {
+    #[cfg(feature = "developer-mode")]
+    enabled: bool,
+    #[cfg(feature = "developer-mode")]
+    durations: [Cell<Duration>; Step::ALL],
+}
+
+/// Adds the time since its creation to the step when dropped
+#[cfg_attr(not(feature = "developer-mode"), allow(dead_code))]
+pub(crate) struct StepTimer<'a> {
+    #[cfg(feature = "developer-mode")]
+    timing: &'a DevTiming,
+    #[cfg(feature = "developer-mode")]
+    step: Step,
+    #[cfg(feature = "developer-mode")]
+    started_at: Instant,
+    #[cfg(feature = "developer-mode")]
+    _span: tracing::span::EnteredSpan,
+    #[cfg(not(feature = "developer-mode"))]
+    _timing: std::marker::PhantomData<&'a DevTiming>,
+}
+
+#[cfg(feature = "developer-mode")]
+impl DevTiming {
+    pub(crate) fn new(enabled: bool) -> anyhow::Result<Self> {
+        Ok(Self {
+            enabled,
+            ..Self::default()
+        })
+    }
+
+    /// Starts timing the step, it lasts until the returned timer is dropped.
+    /// The step is also a `tracing` span, so it shows up in the flame graph
+    pub(crate) fn start(&self, step: Step) -> Option<StepTimer<'_>> {
+        if !self.enabled {
+            return None;
+        }
+        Some(StepTimer {
+            timing: self,
+            step,
+            started_at: Instant::now(),
+            _span: tracing::trace_span!("step", name = step.name()).entered(),
+        })
+    }
+
+    /// Returns the durations of the block and starts over
+    pub(crate) fn take(&self, block_height: BlockHeight) -> Option<DevTimingRecord> {
+        if !self.enabled {
+            return None;
+        }
+        let [stream_receive, shard_iteration, matching, serialization, sink_write] =
+            &self.durations;
+        Some(DevTimingRecord {
+            block_height,
+            stream_receive_us: stream_receive.take().as_micros(),
+            shard_iteration_us: shard_iteration.take().as_micros(),
+            matching_us: matching.take().as_micros(),
+            serialization_us: serialization.take().as_micros(),
+            sink_write_us: sink_write.take().as_micros(),
+        })
+    }
+}
+
+#[cfg(feature = "developer-mode")]
+impl Drop for StepTimer<'_> {
+    fn drop(&mut self) {
+        let duration = &self.timing.durations[self.step as usize];
+        duration.set(duration.get() + self.started_at.elapsed());
+    }
+}
+
+#[cfg(not(feature = "developer-mode"))]
+impl DevTiming {
+    pub(crate) fn new(enabled: bool) -> anyhow::Result<Self> {
+        if enabled {
+            anyhow::bail!(
+                "The watcher was built without the developer mode, rebuild it with `--features developer-mode`"
+            );
+        }
+        Ok(Self::default())
+    }
+
+    #[inline(always)]
+    pub(crate) fn start(&self, _step: Step) -> Option<StepTimer<'_>> {
+        None
+    }
+
+    #[inline(always)]
+    pub(crate) fn take(&self, _block_height: BlockHeight) -> Option<DevTimingRecord> {
+        None
+    }
+}
+
+/// Writes the spans to `tracing.folded` for `scripts/flamegraph.sh`,
+/// the returned guard flushes the file when dropped
+#[cfg(feature = "developer-mode")]
+pub(crate) fn init_flame_layer(
+) -> anyhow::Result<tracing_flame::FlushGuard<std::io::BufWriter<std::fs::File>>> {
+    use tracing_subscriber::prelude::*;
+
+    let (flame_layer, guard) = tracing_flame::FlameLayer::with_file("./tracing.folded")?;
+    tracing_subscriber::registry().with(flame_layer).init();
+    Ok(guard)
+}
@@ -0,0 +1,119 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// Discord allows 50 requests per second
+const REQUESTS_PER_SECOND: f64 = 50.0;
+/// Messages waiting to be posted, the newer ones are dropped when it's full
+const QUEUE_SIZE: usize = 1000;
+/// Discord limit for the value of an embed field
+const MAX_FIELD_CHARS: usize = 1024;
+/// Discord blurple for the informational embeds
+const INFO_COLOR: u32 = 0x5865F2;
+
+/// Posts the matched changes and the critical alerts to the `--alert-discord-webhook`
+/// as embeds from a background task
+pub(crate) struct Discord {
+    sender: tokio::sync::mpsc::Sender<serde_json::Value>,
+    role_mention: Option<String>,
+    critical_color: u32,
+}
+
+impl Discord {
+    pub(crate) fn new(url: String, role_mention: Option<String>, critical_color: u32) -> Self {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(QUEUE_SIZE);
+        let client = reqwest::Client::new();
+        tokio::spawn(async move {
+            let mut rate_limit = TokenBucket::new(REQUESTS_PER_SECOND);
+            while let Some(message) = receiver.recv().await {
+                rate_limit.take().await;
+                if let Err(err) = client
+                    .post(&url)
+                    .json(&message)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                {
+                    eprintln!("Failed to send the alert to Discord: {}", err);
+                }
+            }
+        });
+        Self {
+            sender,
+            role_mention,
+            critical_color,
+        }
+    }
+
+    /// Queues the embed, the `--discord-role-mention` is pinged in the critical ones
+    pub(crate) fn send(
+        &self,
+        block_height: BlockHeight,
+        account_id: &AccountId,
+        change_type: &str,
+        event: &serde_json::Value,
+        is_critical: bool,
+    ) {
+        let mut message = serde_json::json!({
+            "embeds": [{
+                "title": format!("State Change: {}", change_type),
+                "description": format!("Account `{}` at block #{}", account_id, block_height),
+                "color": if is_critical { self.critical_color } else { INFO_COLOR },
+                "fields": [{ "name": "JSON", "value": code_block(event) }],
+            }],
+        });
+        if let Some(role_mention) = self.role_mention.as_ref().filter(|_| is_critical) {
+            message["content"] = format!("<@&{}>", role_mention).into();
+            message["allowed_mentions"] = serde_json::json!({ "roles": [role_mention] });
+        }
+        if self.sender.try_send(message).is_err() {
+            eprintln!(
+                "Discord queue is full, dropping the message about #{}",
+                block_height
+            );
+        }
+    }
+}
+
+/// Formats the JSON as a code block that fits into the embed field limit
+fn code_block(event: &serde_json::Value) -> String {
+    let json = serde_json::to_string_pretty(event).expect("Failed to serialize event to JSON");
+    // Leave room for the backticks and the ellipsis
+    let max_chars = MAX_FIELD_CHARS - 16;
+    let mut snippet: String = json.chars().take(max_chars).collect();
+    if snippet.len() < json.len() {
+        snippet.push('…');
+    }
+    format!("```json\n{}```", snippet)
+}
+
+/// Lets through up to `rate` requests per second with bursts of the same size
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: tokio::time::Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            refilled_at: tokio::time::Instant::now(),
+        }
+    }
+
+    /// Waits until there is a token and takes it
+    async fn take(&mut self) {
+        loop {
+            let now = tokio::time::Instant::now();
+            let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+            self.refilled_at = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return;
+            }
+            let wait = (1.0 - self.tokens) / self.rate;
+            tokio::time::sleep(std::time::Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
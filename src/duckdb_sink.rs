@@ -0,0 +1,92 @@
+//! Local DuckDB database with the matched state changes, so the run can be
+//! explored with SQL. Needs the `duckdb` cargo feature, DuckDB is bundled
+//! and takes a while to compile.
+
+use std::path::Path;
+
+use near_lake_framework::near_indexer_primitives::types::{BlockHeight, ShardId};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+#[cfg(feature = "duckdb")]
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS state_changes (
+    block_height UBIGINT NOT NULL,
+    block_hash VARCHAR NOT NULL,
+    shard_id UBIGINT NOT NULL,
+    account_id VARCHAR NOT NULL,
+    change_type VARCHAR NOT NULL,
+    cause VARCHAR NOT NULL,
+    change VARCHAR NOT NULL
+)";
+
+/// Inserts the matched state changes into the `state_changes` table
+pub(crate) struct DuckDbSink {
+    #[cfg(feature = "duckdb")]
+    connection: duckdb::Connection,
+}
+
+#[cfg(feature = "duckdb")]
+impl DuckDbSink {
+    /// Opens (or creates) the database file, or an in-memory database if there is no path
+    pub(crate) fn open(path: Option<&Path>) -> anyhow::Result<Self> {
+        let connection = match path {
+            Some(path) => duckdb::Connection::open(path)
+                .map_err(|err| anyhow::anyhow!("Failed to open {}: {}", path.display(), err))?,
+            None => duckdb::Connection::open_in_memory()?,
+        };
+        connection.execute_batch(CREATE_TABLE)?;
+        Ok(Self { connection })
+    }
+
+    pub(crate) fn insert(
+        &self,
+        block_height: BlockHeight,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        account_id: &str,
+        change: &serde_json::Value,
+    ) -> anyhow::Result<()> {
+        self.connection.execute(
+            "INSERT INTO state_changes VALUES (?, ?, ?, ?, ?, ?, ?)",
+            duckdb::params![
+                block_height,
+                block_hash.to_string(),
+                shard_id,
+                account_id,
+                change["type"].as_str().unwrap_or_default(),
+                change["cause"].to_string(),
+                change["change"].to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Runs the query and prints the result as a table to stdout
+    pub(crate) fn print_query(&self, sql: &str) -> anyhow::Result<()> {
+        let mut statement = self.connection.prepare(sql)?;
+        let batches: Vec<_> = statement.query_arrow([])?.collect();
+        duckdb::arrow::util::pretty::print_batches(&batches)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "duckdb"))]
+impl DuckDbSink {
+    pub(crate) fn open(_path: Option<&Path>) -> anyhow::Result<Self> {
+        anyhow::bail!("The watcher was built without DuckDB, rebuild it with `--features duckdb`")
+    }
+
+    pub(crate) fn insert(
+        &self,
+        _block_height: BlockHeight,
+        _block_hash: &CryptoHash,
+        _shard_id: ShardId,
+        _account_id: &str,
+        _change: &serde_json::Value,
+    ) -> anyhow::Result<()> {
+        unreachable!("DuckDbSink can't be opened without the duckdb feature")
+    }
+
+    pub(crate) fn print_query(&self, _sql: &str) -> anyhow::Result<()> {
+        unreachable!("DuckDbSink can't be opened without the duckdb feature")
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ExecutionStatusView, ReceiptEnumView, StateChangeValueView,
+};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+/// Chain-wide numbers of a single block (used by `--ecosystem-stats`)
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct EcosystemStats {
+    pub block_height: BlockHeight,
+    pub unique_accounts: usize,
+    pub data_updates: u64,
+    pub access_key_changes: u64,
+    pub contract_deployments: u64,
+    pub account_creations: u64,
+    pub account_deletions: u64,
+    /// Changes not printed because of `--ecosystem-stats-max-rate-limit`
+    pub dropped_changes: u64,
+    #[serde(skip)]
+    accounts: HashSet<AccountId>,
+}
+
+impl EcosystemStats {
+    pub(crate) fn new(block_height: BlockHeight) -> Self {
+        Self {
+            block_height,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn add_state_change(
+        &mut self,
+        account_id: &AccountId,
+        value: &StateChangeValueView,
+    ) {
+        self.accounts.insert(account_id.clone());
+        match value {
+            StateChangeValueView::DataUpdate { .. } | StateChangeValueView::DataDeletion { .. } => {
+                self.data_updates += 1
+            }
+            StateChangeValueView::AccessKeyUpdate { .. }
+            | StateChangeValueView::AccessKeyDeletion { .. } => self.access_key_changes += 1,
+            StateChangeValueView::ContractCodeUpdate { .. } => self.contract_deployments += 1,
+            StateChangeValueView::AccountDeletion { .. } => self.account_deletions += 1,
+            StateChangeValueView::AccountUpdate { .. }
+            | StateChangeValueView::ContractCodeDeletion { .. } => {}
+        }
+    }
+
+    /// The state changes don't tell a new account from an updated one,
+    /// so we count the successful `CreateAccount` actions instead
+    pub(crate) fn add_receipt(&mut self, outcome: &IndexerExecutionOutcomeWithReceipt) {
+        let is_successful = matches!(
+            outcome.execution_outcome.outcome.status,
+            ExecutionStatusView::SuccessValue(_) | ExecutionStatusView::SuccessReceiptId(_)
+        );
+        if let ReceiptEnumView::Action { actions, .. } = &outcome.receipt.receipt {
+            if is_successful
+                && actions
+                    .iter()
+                    .any(|action| matches!(action, ActionView::CreateAccount))
+            {
+                self.account_creations += 1;
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Self {
+        self.unique_accounts = self.accounts.len();
+        self
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight, ShardId};
+
+use crate::configs::EventOrdering;
+
+/// A matched change held back until the end of the block (used by `--event-ordering`)
+pub(crate) struct MatchedEvent {
+    /// The `type` printed before the change
+    header: serde_json::Value,
+    json: serde_json::Value,
+    shard_id: ShardId,
+    /// Position of the first event of the same account in the block,
+    /// the accounts keep the order they first appeared in
+    account_position: usize,
+    /// Position of the change type in the `--event-type-priority`
+    type_priority: usize,
+    /// Position of the event in the block
+    position: usize,
+}
+
+impl MatchedEvent {
+    pub(crate) fn sort_key(&self, ordering: &EventOrdering) -> impl Ord {
+        match ordering {
+            EventOrdering::Type => (
+                self.account_position as u64,
+                self.type_priority,
+                self.position,
+            ),
+            EventOrdering::Shard => (self.shard_id, 0, self.position),
+            EventOrdering::Natural => (0, 0, self.position),
+        }
+    }
+}
+
+/// The matched changes of a block in the order they were found
+#[derive(Default)]
+pub(crate) struct BlockEvents {
+    events: Vec<MatchedEvent>,
+    account_positions: HashMap<AccountId, usize>,
+}
+
+impl BlockEvents {
+    pub(crate) fn push(
+        &mut self,
+        account_id: &AccountId,
+        shard_id: ShardId,
+        type_priority: usize,
+        header: serde_json::Value,
+        json: serde_json::Value,
+    ) {
+        let position = self.events.len();
+        let account_position = *self
+            .account_positions
+            .entry(account_id.clone())
+            .or_insert(position);
+        self.events.push(MatchedEvent {
+            header,
+            json,
+            shard_id,
+            account_position,
+            type_priority,
+            position,
+        });
+    }
+
+    pub(crate) fn print_sorted(mut self, block_height: BlockHeight, ordering: &EventOrdering) {
+        self.events
+            .sort_unstable_by_key(|event| event.sort_key(ordering));
+        for event in self.events {
+            println!("#{}. {}", block_height, event.header);
+            println!("{:#?}", event.json);
+        }
+    }
+}
+
+/// Types missing from the `--event-type-priority` come after the listed ones
+pub(crate) fn type_priority(priorities: &[String], change_type: &str) -> usize {
+    priorities
+        .iter()
+        .position(|priority| priority == change_type)
+        .unwrap_or(priorities.len())
+}
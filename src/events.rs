@@ -0,0 +1,25 @@
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+/// Contract event log in NEP-297 format
+/// ref https://nomicon.io/Standards/EventsFormat
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct Nep297Event {
+    pub standard: String,
+    pub event: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// Parses all the NEP-297 events the receipt has logged during the execution
+pub(crate) fn receipt_events(outcome: &IndexerExecutionOutcomeWithReceipt) -> Vec<Nep297Event> {
+    outcome
+        .execution_outcome
+        .outcome
+        .logs
+        .iter()
+        .filter_map(|log| log.strip_prefix(EVENT_JSON_PREFIX))
+        .filter_map(|event_json| serde_json::from_str(event_json).ok())
+        .collect()
+}
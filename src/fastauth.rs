@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+use near_lake_framework::near_indexer_primitives::views::{
+    AccessKeyPermissionView, StateChangeCauseView, StateChangeValueView, StateChangeWithCauseView,
+};
+
+/// A passkey has been added to or removed from a FastAuth account
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct FastAuthPasskeyEvent {
+    #[serde(skip)]
+    pub event_type: &'static str,
+    pub account_id: AccountId,
+    pub public_key: String,
+    pub public_key_type: String,
+    /// The account that sent the transaction or the receipt adding or removing the key,
+    /// if we have it in the block
+    pub relayer: Option<AccountId>,
+}
+
+/// Recognizes the FastAuth passkeys by their function call permission for the
+/// FastAuth authenticator contract. A deleted key has no permission anymore, so
+/// we remember the passkeys we have seen added and report only their removal
+#[derive(Debug, Default)]
+pub(crate) struct FastAuthTracker {
+    /// Public key type of each known passkey
+    passkeys: HashMap<(AccountId, String), String>,
+}
+
+impl FastAuthTracker {
+    pub(crate) fn check(
+        &mut self,
+        state_change: &StateChangeWithCauseView,
+        authenticator: &AccountId,
+    ) -> Option<FastAuthPasskeyEvent> {
+        match &state_change.value {
+            StateChangeValueView::AccessKeyUpdate {
+                account_id,
+                public_key,
+                access_key,
+            } => {
+                let is_passkey = matches!(
+                    &access_key.permission,
+                    AccessKeyPermissionView::FunctionCall { receiver_id, .. }
+                        if receiver_id == authenticator.as_ref()
+                );
+                let passkey = (account_id.clone(), public_key.to_string());
+                if !is_passkey {
+                    // The key might have been replaced with a key of another kind
+                    self.passkeys.remove(&passkey);
+                    return None;
+                }
+                // Signing a transaction updates the nonce of the key, the keys
+                // are added by the `AddKey` action of a receipt
+                if let StateChangeCauseView::TransactionProcessing { .. } = state_change.cause {
+                    return None;
+                }
+                let public_key_type = public_key.key_type().to_string();
+                self.passkeys.insert(passkey, public_key_type.clone());
+                Some(FastAuthPasskeyEvent {
+                    event_type: "fastauth_passkey_added",
+                    account_id: account_id.clone(),
+                    public_key: public_key.to_string(),
+                    public_key_type,
+                    relayer: None,
+                })
+            }
+            StateChangeValueView::AccessKeyDeletion {
+                account_id,
+                public_key,
+            } => {
+                let public_key_type = self
+                    .passkeys
+                    .remove(&(account_id.clone(), public_key.to_string()))?;
+                Some(FastAuthPasskeyEvent {
+                    event_type: "fastauth_passkey_removed",
+                    account_id: account_id.clone(),
+                    public_key: public_key.to_string(),
+                    public_key_type,
+                    relayer: None,
+                })
+            }
+            _ => None,
+        }
+    }
+}
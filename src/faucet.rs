@@ -0,0 +1,102 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ExecutionStatusView, ReceiptEnumView, StateChangeCauseView, StateChangeValueView,
+};
+use near_lake_framework::near_indexer_primitives::{
+    IndexerExecutionOutcomeWithReceipt, StreamerMessage,
+};
+
+/// Number of yoctoNEAR in 1 NEAR
+const YOCTO_PER_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// NEAR sent by the faucet (used by `--faucet-tracking`)
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct FaucetEvent {
+    pub recipient_account: AccountId,
+    pub amount_near: String,
+    pub amount_yocto: String,
+    /// The `AccountUpdate` of the recipient caused by the transfer is in the same block
+    pub confirmed: bool,
+    pub receipt_id: String,
+    pub block_height: BlockHeight,
+}
+
+/// The faucet's own `AccountUpdate` goes down by the distributed amount
+/// but doesn't say who has got it, so we look at the `Transfer` receipts
+/// sent by the faucet instead
+pub(crate) struct FaucetDecoder {
+    faucet_account: AccountId,
+}
+
+impl FaucetDecoder {
+    pub(crate) fn new(faucet_account: AccountId) -> Self {
+        Self { faucet_account }
+    }
+
+    pub(crate) fn detect_distribution(
+        &self,
+        outcome: &IndexerExecutionOutcomeWithReceipt,
+        streamer_message: &StreamerMessage,
+    ) -> Option<FaucetEvent> {
+        if outcome.receipt.predecessor_id != self.faucet_account {
+            return None;
+        }
+        if !matches!(
+            outcome.execution_outcome.outcome.status,
+            ExecutionStatusView::SuccessValue(_) | ExecutionStatusView::SuccessReceiptId(_)
+        ) {
+            return None;
+        }
+        let actions = match &outcome.receipt.receipt {
+            ReceiptEnumView::Action { actions, .. } => actions,
+            ReceiptEnumView::Data { .. } => return None,
+        };
+        let amount: u128 = actions
+            .iter()
+            .map(|action| match action {
+                ActionView::Transfer { deposit } => *deposit,
+                _ => 0,
+            })
+            .sum();
+        if amount == 0 {
+            return None;
+        }
+        let recipient_account = outcome.receipt.receiver_id.clone();
+        let confirmed = streamer_message
+            .shards
+            .iter()
+            .flat_map(|shard| shard.state_changes.iter())
+            .any(|state_change| {
+                matches!(
+                    (&state_change.value, &state_change.cause),
+                    (
+                        StateChangeValueView::AccountUpdate { account_id, .. },
+                        StateChangeCauseView::ReceiptProcessing { receipt_hash },
+                    ) if *account_id == recipient_account
+                        && *receipt_hash == outcome.receipt.receipt_id
+                )
+            });
+        Some(FaucetEvent {
+            recipient_account,
+            amount_near: format_near(amount),
+            amount_yocto: amount.to_string(),
+            confirmed,
+            receipt_id: outcome.receipt.receipt_id.to_string(),
+            block_height: streamer_message.block.header.height,
+        })
+    }
+}
+
+/// Formats yoctoNEAR as NEAR without the trailing zeros, e.g. `10.5`
+fn format_near(amount: u128) -> String {
+    let fraction = amount % YOCTO_PER_NEAR;
+    if fraction == 0 {
+        return (amount / YOCTO_PER_NEAR).to_string();
+    }
+    let fraction = format!("{:024}", fraction);
+    format!(
+        "{}.{}",
+        amount / YOCTO_PER_NEAR,
+        fraction.trim_end_matches('0')
+    )
+}
@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// Counts how many StateChanges each watched account had
+/// since the last report
+#[derive(Debug, Default)]
+pub(crate) struct ChangeFrequencyTracker {
+    changes: BTreeMap<AccountId, u64>,
+}
+
+impl ChangeFrequencyTracker {
+    pub(crate) fn record(&mut self, account_id: &AccountId) {
+        *self.changes.entry(account_id.clone()).or_insert(0) += 1;
+    }
+
+    /// Returns up to `n` accounts with the most changes, the most active first
+    pub(crate) fn top_n(&self, n: usize) -> Vec<(AccountId, u64)> {
+        let mut top: Vec<(AccountId, u64)> = self
+            .changes
+            .iter()
+            .map(|(account_id, count)| (account_id.clone(), *count))
+            .collect();
+        // BTreeMap gives us accounts in alphabetical order,
+        // stable sort keeps it for the accounts with the same count
+        top.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        top.truncate(n);
+        top
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.changes.clear();
+    }
+}
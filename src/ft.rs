@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+use crate::events;
+
+/// Symbol and decimals of the token from the `--ft-token-list` file
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct TokenInfo {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+/// Token contract id to token info mapping
+#[derive(Debug, Default)]
+pub(crate) struct FtTokenList {
+    tokens: HashMap<String, TokenInfo>,
+}
+
+impl FtTokenList {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", path.display(), err))?;
+        Ok(Self {
+            tokens: serde_json::from_str(&content)?,
+        })
+    }
+
+    pub(crate) fn get(&self, token_contract: &str) -> Option<&TokenInfo> {
+        self.tokens.get(token_contract)
+    }
+}
+
+/// NEP-141 `ft_transfer` event
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct FtTransferEvent {
+    pub token_contract: AccountId,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_human: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct FtTransferData {
+    old_owner_id: String,
+    new_owner_id: String,
+    amount: String,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+/// Returns the `ft_transfer` events the token contract has logged in the receipt,
+/// the events are enriched with the symbol and the human-readable amount
+/// if the token list is provided
+pub(crate) fn ft_transfer_events(
+    outcome: &IndexerExecutionOutcomeWithReceipt,
+    token_list: Option<&FtTokenList>,
+) -> Vec<FtTransferEvent> {
+    events::receipt_events(outcome)
+        .into_iter()
+        .filter(|event| event.standard == "nep141" && event.event == "ft_transfer")
+        .filter_map(|event| serde_json::from_value::<Vec<FtTransferData>>(event.data).ok())
+        .flatten()
+        .map(|transfer| {
+            let token_contract = outcome.receipt.receiver_id.clone();
+            let (symbol, amount_human) =
+                match token_list.map(|token_list| token_list.get(token_contract.as_ref())) {
+                    Some(Some(token_info)) => (
+                        Some(token_info.symbol.clone()),
+                        Some(
+                            transfer
+                                .amount
+                                .parse::<u128>()
+                                .map(|amount| format_amount(amount, token_info.decimals))
+                                .unwrap_or_else(|_| "?".to_string()),
+                        ),
+                    ),
+                    // The token is not in the list
+                    Some(None) => (Some("UNKNOWN".to_string()), Some("?".to_string())),
+                    None => (None, None),
+                };
+            FtTransferEvent {
+                token_contract,
+                old_owner_id: transfer.old_owner_id,
+                new_owner_id: transfer.new_owner_id,
+                amount: transfer.amount,
+                memo: transfer.memo,
+                symbol,
+                amount_human,
+            }
+        })
+        .collect()
+}
+
+/// Formats the amount in the smallest units with the given number of decimal places,
+/// e.g. `1500000` with 6 decimals is `1.500000`
+pub(crate) fn format_amount(amount: u128, decimals: u32) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let digits = format!("{:0>width$}", amount, width = decimals as usize + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals as usize);
+    format!("{}.{}", integer, fraction)
+}
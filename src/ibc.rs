@@ -0,0 +1,190 @@
+use near_lake_framework::near_indexer_primitives::views::StateChangeValueView;
+
+/// IBC event recognized by the ICS-24 path of the IBC contract's storage key
+/// ref https://github.com/cosmos/ibc/tree/main/spec/core/ics-024-host-requirements#path-space
+/// The counterparty chain is stored in the protobuf-encoded channel end,
+/// which we don't decode, so the events carry the port and the channel only
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct IbcEvent {
+    #[serde(skip)]
+    pub kind: IbcEventKind,
+    pub port_id: String,
+    pub channel_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum IbcEventKind {
+    ChannelOpen,
+    PacketSent,
+    PacketAcked,
+}
+
+impl IbcEventKind {
+    pub(crate) fn event_type(&self) -> &'static str {
+        match self {
+            IbcEventKind::ChannelOpen => "ibc_channel_open",
+            IbcEventKind::PacketSent => "ibc_packet_sent",
+            IbcEventKind::PacketAcked => "ibc_packet_acked",
+        }
+    }
+}
+
+pub(crate) struct IbcKeyParser;
+
+impl IbcKeyParser {
+    /// Recognizes the ICS-24 path in the storage key:
+    /// - `channelEnds/ports/{port}/channels/{channel}` is a channel being opened
+    /// - `commitments/ports/{port}/channels/{channel}/sequences/{sequence}` is a packet sent
+    /// - `acks/ports/{port}/channels/{channel}/sequences/{sequence}` is a packet acknowledged
+    pub(crate) fn parse(key: &[u8]) -> Option<IbcEvent> {
+        let key = String::from_utf8_lossy(key);
+        // The contract might prefix the path with its own storage prefix
+        let (kind, path) = [
+            ("channelEnds/", IbcEventKind::ChannelOpen),
+            ("commitments/", IbcEventKind::PacketSent),
+            ("acks/", IbcEventKind::PacketAcked),
+        ]
+        .iter()
+        .find_map(|(prefix, kind)| {
+            key.find(prefix)
+                .map(|position| (*kind, &key[position + prefix.len()..]))
+        })?;
+
+        let segments: Vec<&str> = path.split('/').collect();
+        match (kind, segments.as_slice()) {
+            (IbcEventKind::ChannelOpen, ["ports", port_id, "channels", channel_id]) => {
+                Some(IbcEvent {
+                    kind,
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                    sequence: None,
+                })
+            }
+            (
+                IbcEventKind::PacketSent | IbcEventKind::PacketAcked,
+                ["ports", port_id, "channels", channel_id, "sequences", sequence],
+            ) => Some(IbcEvent {
+                kind,
+                port_id: port_id.to_string(),
+                channel_id: channel_id.to_string(),
+                sequence: Some(sequence.parse().ok()?),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Only the new entries are events, deletions of the commitments are
+/// the contract's cleanup after the acknowledgement
+pub(crate) fn ibc_event(value: &StateChangeValueView) -> Option<IbcEvent> {
+    match value {
+        StateChangeValueView::DataUpdate { key, .. } => IbcKeyParser::parse(key.as_ref()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(key: &str) -> Option<(&'static str, String, String, Option<u64>)> {
+        IbcKeyParser::parse(key.as_bytes()).map(|event| {
+            (
+                event.kind.event_type(),
+                event.port_id,
+                event.channel_id,
+                event.sequence,
+            )
+        })
+    }
+
+    #[test]
+    fn parses_channel_end() {
+        assert_eq!(
+            parse("channelEnds/ports/transfer/channels/channel-0"),
+            Some((
+                "ibc_channel_open",
+                "transfer".to_string(),
+                "channel-0".to_string(),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_packet_commitment() {
+        assert_eq!(
+            parse("commitments/ports/transfer/channels/channel-1/sequences/42"),
+            Some((
+                "ibc_packet_sent",
+                "transfer".to_string(),
+                "channel-1".to_string(),
+                Some(42)
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_packet_ack() {
+        assert_eq!(
+            parse("acks/ports/transfer/channels/channel-1/sequences/7"),
+            Some((
+                "ibc_packet_acked",
+                "transfer".to_string(),
+                "channel-1".to_string(),
+                Some(7)
+            ))
+        );
+    }
+
+    #[test]
+    fn ignores_packet_receipts() {
+        assert_eq!(
+            parse("receipts/ports/transfer/channels/channel-1/sequences/7"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_contract_prefixed_keys() {
+        assert_eq!(
+            parse("ibc/commitments/ports/transfer/channels/channel-2/sequences/3"),
+            Some((
+                "ibc_packet_sent",
+                "transfer".to_string(),
+                "channel-2".to_string(),
+                Some(3)
+            ))
+        );
+        let mut key = vec![0x00, 0x01];
+        key.extend_from_slice(b"channelEnds/ports/transfer/channels/channel-5");
+        assert_eq!(
+            IbcKeyParser::parse(&key).map(|event| event.channel_id),
+            Some("channel-5".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_non_numeric_sequences() {
+        assert_eq!(
+            parse("commitments/ports/transfer/channels/channel-1/sequences/abc"),
+            None
+        );
+        assert_eq!(
+            parse("acks/ports/transfer/channels/channel-1/sequences/-1"),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_paths() {
+        assert_eq!(parse("channelEnds/ports/transfer"), None);
+        assert_eq!(parse("commitments/ports/transfer/channels/channel-1"), None);
+        assert_eq!(
+            parse("channelEnds/ports/transfer/channels/channel-0/sequences/1"),
+            None
+        );
+    }
+}
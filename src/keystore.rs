@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{
+    AccessKeyPermissionView, StateChangeCauseView, StateChangeValueView,
+};
+
+/// A full access key has been added to the watched account
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct KeystoreAlert {
+    pub account_id: AccountId,
+    pub public_key: String,
+    /// `critical` unless the key is in `--allowed-full-access-keys`
+    pub severity: &'static str,
+    pub block_height: BlockHeight,
+}
+
+impl KeystoreAlert {
+    pub(crate) fn is_critical(&self) -> bool {
+        self.severity == "critical"
+    }
+}
+
+/// Returns the alert if the change adds a full access key. Signing a transaction
+/// updates the nonce of the key as well, those updates don't add anything and are skipped
+pub(crate) fn full_access_key_alert(
+    value: &StateChangeValueView,
+    cause: &StateChangeCauseView,
+    allowed_keys: &HashSet<String>,
+    block_height: BlockHeight,
+) -> Option<KeystoreAlert> {
+    let (account_id, public_key) = match (value, cause) {
+        (_, StateChangeCauseView::TransactionProcessing { .. }) => return None,
+        (
+            StateChangeValueView::AccessKeyUpdate {
+                account_id,
+                public_key,
+                access_key,
+            },
+            _,
+        ) if matches!(access_key.permission, AccessKeyPermissionView::FullAccess) => {
+            (account_id, public_key.to_string())
+        }
+        _ => return None,
+    };
+    Some(KeystoreAlert {
+        account_id: account_id.clone(),
+        severity: if allowed_keys.contains(&public_key) {
+            "info"
+        } else {
+            "critical"
+        },
+        public_key,
+        block_height,
+    })
+}
@@ -0,0 +1,102 @@
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+use near_lake_framework::near_indexer_primitives::StreamerMessage;
+
+/// Reads the index file some NEAR Lake buckets have next to the blocks: a text file
+/// with the heights of the blocks that have state changes, one per line.
+/// The blocks missing from it are never fetched, that's what makes scanning
+/// the quiet periods of the history fast
+pub(crate) struct BlockIndexReader {
+    client: aws_sdk_s3::Client,
+    s3_bucket_name: String,
+}
+
+impl BlockIndexReader {
+    pub(crate) fn new(client: aws_sdk_s3::Client, s3_bucket_name: String) -> Self {
+        Self {
+            client,
+            s3_bucket_name,
+        }
+    }
+
+    /// Returns the indexed heights from the start height in ascending order
+    pub(crate) async fn fetch(
+        &self,
+        index_key: &str,
+        start_block_height: BlockHeight,
+    ) -> anyhow::Result<Vec<BlockHeight>> {
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.s3_bucket_name)
+            .key(index_key)
+            .request_payer(aws_sdk_s3::model::RequestPayer::Requester)
+            .send()
+            .await?;
+        let body_bytes = response.body.collect().await?.into_bytes();
+        let mut heights = std::str::from_utf8(&body_bytes)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.parse::<BlockHeight>()
+                    .map_err(|err| anyhow::anyhow!("Invalid height {}: {}", line, err))
+            })
+            .filter(|height| {
+                height
+                    .as_ref()
+                    .map_or(true, |height| *height >= start_block_height)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        heights.sort_unstable();
+        heights.dedup();
+        Ok(heights)
+    }
+
+    /// Fetches the indexed blocks one by one, then starts the NEAR Lake streamer
+    /// after the last of them, since the index lags behind the tip of the chain
+    pub(crate) fn indexed_stream(
+        self,
+        heights: Vec<BlockHeight>,
+        mut config: near_lake_framework::LakeConfig,
+        verify_checksums: bool,
+        retry_policy: crate::retry::RetryPolicy,
+    ) -> tokio::sync::mpsc::Receiver<StreamerMessage> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(100);
+        tokio::spawn(async move {
+            let mut checksum_failures_total = 0u64;
+            for height in heights {
+                let result = retry_policy
+                    .run(|| {
+                        crate::block_range::fetch_streamer_message(
+                            &self.client,
+                            &self.s3_bucket_name,
+                            height,
+                            verify_checksums,
+                        )
+                    })
+                    .await;
+                match result {
+                    Ok(streamer_message) => {
+                        if sender.send(streamer_message).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) => crate::block_range::report_skipped_block(
+                        height,
+                        err,
+                        &mut checksum_failures_total,
+                    ),
+                }
+                config.start_block_height = height + 1;
+            }
+
+            let (_, mut stream) = near_lake_framework::streamer(config);
+            while let Some(streamer_message) = stream.recv().await {
+                if sender.send(streamer_message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
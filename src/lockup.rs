@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, Balance, BlockHeight};
+
+const LOCKUP_ACCOUNT_SUFFIX: &str = ".lockup.near";
+
+/// The contract struct is stored under this key by near-sdk
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Lockup account's tokens have vested
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct LockupVestingEvent {
+    pub account_id: AccountId,
+    pub vested_amount: String,
+    /// The tokens that are still unvested
+    pub remaining_locked: String,
+    /// The pool the lockup delegates to and the amount deposited there, if any
+    pub staking_pool_account_id: Option<String>,
+    pub staking_pool_deposit: Option<String>,
+    pub block_height: BlockHeight,
+}
+
+/// The `LockupContract` struct of the reference lockup contract
+/// ref https://github.com/near/core-contracts/blob/master/lockup/src/lib.rs
+#[derive(BorshDeserialize)]
+struct LockupContractState {
+    _owner_account_id: String,
+    lockup_information: LockupInformation,
+    vesting_information: VestingInformation,
+    _staking_pool_whitelist_account_id: String,
+    staking_information: Option<StakingInformation>,
+    _foundation_account_id: Option<String>,
+}
+
+#[derive(BorshDeserialize)]
+struct LockupInformation {
+    lockup_amount: Balance,
+    _termination_withdrawn_tokens: Balance,
+    _lockup_duration: u64,
+    _release_duration: Option<u64>,
+    _lockup_timestamp: Option<u64>,
+    _transfers_information: TransfersInformation,
+}
+
+#[derive(BorshDeserialize)]
+enum TransfersInformation {
+    TransfersEnabled { _transfers_timestamp: u64 },
+    TransfersDisabled { _transfer_poll_account_id: String },
+}
+
+#[derive(BorshDeserialize)]
+enum VestingInformation {
+    None,
+    /// The schedule is private, only its hash is stored
+    VestingHash {
+        _hash: Vec<u8>,
+    },
+    VestingSchedule(VestingSchedule),
+    Terminating(TerminationInformation),
+}
+
+#[derive(BorshDeserialize)]
+struct VestingSchedule {
+    start_timestamp: u64,
+    cliff_timestamp: u64,
+    end_timestamp: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct TerminationInformation {
+    unvested_amount: Balance,
+    _status: u8,
+}
+
+#[derive(BorshDeserialize)]
+struct StakingInformation {
+    staking_pool_account_id: String,
+    _status: u8,
+    deposit_amount: Balance,
+}
+
+impl LockupContractState {
+    /// Same as `get_unvested_amount` of the contract, `None` for the private schedules
+    fn unvested_amount(&self, block_timestamp: u64) -> Option<Balance> {
+        let lockup_amount = self.lockup_information.lockup_amount;
+        match &self.vesting_information {
+            VestingInformation::None => Some(0),
+            VestingInformation::VestingHash { .. } => None,
+            VestingInformation::Terminating(termination) => Some(termination.unvested_amount),
+            VestingInformation::VestingSchedule(schedule) => {
+                if block_timestamp < schedule.cliff_timestamp {
+                    Some(lockup_amount)
+                } else if block_timestamp >= schedule.end_timestamp {
+                    Some(0)
+                } else {
+                    // The product doesn't fit into u128
+                    let time_left = schedule.end_timestamp - block_timestamp;
+                    let total_time = schedule.end_timestamp - schedule.start_timestamp;
+                    let unvested = ethabi::Uint::from(lockup_amount)
+                        * ethabi::Uint::from(time_left)
+                        / ethabi::Uint::from(total_time);
+                    Some(unvested.as_u128())
+                }
+            }
+        }
+    }
+}
+
+/// Decodes the state of the watched lockup contracts and remembers
+/// their unvested amount to compare it with the next state update
+#[derive(Debug, Default)]
+pub(crate) struct LockupTracker {
+    unvested_amounts: HashMap<AccountId, Balance>,
+}
+
+impl LockupTracker {
+    pub(crate) fn is_lockup_account(account_id: &AccountId) -> bool {
+        account_id.as_ref().ends_with(LOCKUP_ACCOUNT_SUFFIX)
+    }
+
+    /// Returns the vesting event if the unvested amount has decreased since
+    /// the previous state update of the account. The vesting goes on between
+    /// the updates, so the event covers everything vested meanwhile
+    pub(crate) fn check(
+        &mut self,
+        account_id: &AccountId,
+        key: &[u8],
+        value: &[u8],
+        block_timestamp: u64,
+        block_height: BlockHeight,
+    ) -> Option<LockupVestingEvent> {
+        if key != STATE_KEY {
+            return None;
+        }
+        let state = LockupContractState::try_from_slice(value).ok()?;
+        let unvested_amount = state.unvested_amount(block_timestamp)?;
+        let previous_unvested = self
+            .unvested_amounts
+            .insert(account_id.clone(), unvested_amount)?;
+        if unvested_amount >= previous_unvested {
+            return None;
+        }
+        Some(LockupVestingEvent {
+            account_id: account_id.clone(),
+            vested_amount: (previous_unvested - unvested_amount).to_string(),
+            remaining_locked: unvested_amount.to_string(),
+            staking_pool_account_id: state
+                .staking_information
+                .as_ref()
+                .map(|staking| staking.staking_pool_account_id.clone()),
+            staking_pool_deposit: state
+                .staking_information
+                .map(|staking| staking.deposit_amount.to_string()),
+            block_height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+
+    use super::*;
+
+    const LOCKUP_AMOUNT: Balance = 1_000_000_000_000_000_000_000_000_000;
+
+    /// Serializes the state how the contract does it, field by field
+    fn state(vesting_information: &[u8], staking_pool: Option<(&str, Balance)>) -> Vec<u8> {
+        let mut state = vec![];
+        "owner.near".serialize(&mut state).unwrap();
+        LOCKUP_AMOUNT.serialize(&mut state).unwrap();
+        0u128.serialize(&mut state).unwrap();
+        0u64.serialize(&mut state).unwrap();
+        None::<u64>.serialize(&mut state).unwrap();
+        None::<u64>.serialize(&mut state).unwrap();
+        // TransfersEnabled
+        0u8.serialize(&mut state).unwrap();
+        0u64.serialize(&mut state).unwrap();
+        state.extend_from_slice(vesting_information);
+        "lockup-whitelist.near".serialize(&mut state).unwrap();
+        match staking_pool {
+            Some((pool, deposit)) => {
+                1u8.serialize(&mut state).unwrap();
+                pool.serialize(&mut state).unwrap();
+                // TransactionStatus::Idle
+                0u8.serialize(&mut state).unwrap();
+                deposit.serialize(&mut state).unwrap();
+            }
+            None => 0u8.serialize(&mut state).unwrap(),
+        }
+        Some("foundation.near").serialize(&mut state).unwrap();
+        state
+    }
+
+    fn vesting_schedule(start: u64, cliff: u64, end: u64) -> Vec<u8> {
+        let mut vesting = vec![2u8];
+        for timestamp in [start, cliff, end] {
+            timestamp.serialize(&mut vesting).unwrap();
+        }
+        vesting
+    }
+
+    fn account_id() -> AccountId {
+        "abc.lockup.near".parse().unwrap()
+    }
+
+    #[test]
+    fn reports_vesting_between_state_updates() {
+        let mut tracker = LockupTracker::default();
+        let value = state(
+            &vesting_schedule(0, 100, 1000),
+            Some(("zavodil.poolv1.near", 500)),
+        );
+        // Before the cliff nothing has vested
+        assert!(tracker
+            .check(&account_id(), STATE_KEY, &value, 50, 1)
+            .is_none());
+        let event = tracker
+            .check(&account_id(), STATE_KEY, &value, 250, 2)
+            .unwrap();
+        assert_eq!(event.vested_amount, (LOCKUP_AMOUNT / 4).to_string());
+        assert_eq!(event.remaining_locked, (LOCKUP_AMOUNT / 4 * 3).to_string());
+        assert_eq!(
+            event.staking_pool_account_id.as_deref(),
+            Some("zavodil.poolv1.near")
+        );
+        assert_eq!(event.staking_pool_deposit.as_deref(), Some("500"));
+        // Everything is vested after the end
+        let event = tracker
+            .check(&account_id(), STATE_KEY, &value, 1000, 3)
+            .unwrap();
+        assert_eq!(event.remaining_locked, "0");
+    }
+
+    #[test]
+    fn reports_termination() {
+        let mut tracker = LockupTracker::default();
+        let value = state(&vesting_schedule(0, 100, 1000), None);
+        assert!(tracker
+            .check(&account_id(), STATE_KEY, &value, 0, 1)
+            .is_none());
+        // Terminated with 10 tokens still unvested, `VestingTerminatedWithDeficit`
+        let mut terminating = vec![3u8];
+        10u128.serialize(&mut terminating).unwrap();
+        terminating.push(0);
+        let event = tracker
+            .check(&account_id(), STATE_KEY, &state(&terminating, None), 10, 2)
+            .unwrap();
+        assert_eq!(event.remaining_locked, "10");
+        assert_eq!(event.staking_pool_account_id, None);
+    }
+
+    #[test]
+    fn skips_private_schedules_and_other_keys() {
+        let mut tracker = LockupTracker::default();
+        let mut vesting_hash = vec![1u8];
+        vec![7u8; 32].serialize(&mut vesting_hash).unwrap();
+        let value = state(&vesting_hash, None);
+        assert!(tracker
+            .check(&account_id(), STATE_KEY, &value, 0, 1)
+            .is_none());
+        assert!(tracker.unvested_amounts.is_empty());
+        let value = state(&vesting_schedule(0, 100, 1000), None);
+        assert!(tracker
+            .check(&account_id(), b"other", &value, 0, 1)
+            .is_none());
+        assert!(tracker
+            .check(&account_id(), STATE_KEY, &value[..value.len() - 1], 0, 1)
+            .is_none());
+    }
+}
@@ -1,31 +1,178 @@
-use clap::Parser;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use futures::FutureExt;
+
+use clap::CommandFactory;
 use near_lake_framework::near_indexer_primitives::types::AccountId;
 use near_lake_framework::near_indexer_primitives::views::{
-    StateChangeValueView, StateChangeWithCauseView,
+    AccountView, StateChangeCauseView, StateChangeValueView, StateChangeWithCauseView,
 };
-use near_lake_framework::LakeConfigBuilder;
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+use configs::{ChainId, Command, EventOrdering, Opts};
 
-#[derive(Parser)]
-#[clap(author = "Near Inc. <hello@nearprotocol.com")]
-pub(crate) struct Opts {
-    #[clap(long, short)]
-    pub accounts: Vec<AccountId>,
-    #[clap(long, short)]
-    pub block_height: u64,
-    #[clap(subcommand)]
-    pub chain_id: ChainId,
+mod abi_check;
+mod account_birth;
+mod account_history;
+mod alert_silence;
+mod astro_dao;
+mod async_writes;
+mod aurora;
+mod aurora_bridge;
+mod aurora_tx;
+mod block_index;
+mod block_range;
+mod block_time;
+mod burrow;
+mod chain_signatures;
+mod configs;
+mod consistent_hash;
+mod contract_size;
+mod delta;
+mod dev_timing;
+mod discord;
+mod duckdb_sink;
+mod ecosystem_stats;
+mod event_ordering;
+mod events;
+mod fastauth;
+mod faucet;
+mod frequency;
+mod ft;
+mod ibc;
+mod keystore;
+mod lake_index;
+mod lockup;
+mod memory_cap;
+mod namespace_stats;
+mod near_transfer;
+mod object_cache;
+mod oracle;
+mod pagerduty;
+mod projection;
+mod receipt_delay;
+mod ref_finance;
+mod retry;
+mod rpc;
+mod s3;
+mod shard_stats;
+mod slack;
+mod social;
+mod staking_pool;
+mod storage_alert;
+mod tx_pattern;
+mod validator_set;
+mod wallet;
+mod wasm_plugin;
+mod webhook;
+
+/// The things we prepare from the options once at the start
+struct WatcherConfig {
+    denylist: regex::RegexSet,
+    aurora_abi: Option<ethabi::Contract>,
+    /// Used by `--presign-matched-blocks`
+    presigner: Option<s3::Presigner>,
+    /// DAOs from the `--dao-accounts-file`
+    dao_accounts: std::collections::HashSet<AccountId>,
+    /// Keys from the `--allowed-full-access-keys`
+    allowed_full_access_keys: std::collections::HashSet<String>,
+    /// Used by `--alert-webhook`
+    alert_webhook: Option<webhook::Webhook>,
+    /// Used by `--alert-slack-webhook`
+    slack: Option<slack::Slack>,
+    /// Used by `--alert-discord-webhook`
+    discord: Option<discord::Discord>,
+    /// Used by `--abi-check`
+    abi_validator: Option<abi_check::NearAbiValidator>,
+    /// Used by `--validator-set-tracking`
+    validator_rpc: Option<rpc::JsonRpcClient>,
+    /// Used by `--faucet-tracking`
+    faucet: Option<faucet::FaucetDecoder>,
+    /// Used by `--tx-pattern`
+    tx_pattern: Option<tx_pattern::TxPattern>,
+    /// Used by `--aurora-tx-tracking`
+    aurora_tx: Option<aurora_tx::AuroraTxDecoder>,
+    /// Used by `--consistent-hash-shard`
+    hash_ring: Option<consistent_hash::HashRing>,
 }
 
-#[derive(Parser)]
-pub(crate) enum ChainId {
-    Mainnet,
-    Testnet,
+/// The data we need to carry from one block to another
+#[derive(Default)]
+struct WatcherState {
+    /// The last `AccountView` we have seen for each watched account
+    /// (used by `--delta-encoding`)
+    last_account_views: HashMap<AccountId, AccountView>,
+    /// Number of changes per watched account (used by `--change-frequency-report`)
+    change_frequency: frequency::ChangeFrequencyTracker,
+    /// Used by `--namespace-stats`
+    namespace_stats: namespace_stats::NamespaceStatsTracker,
+    /// The last seen value of each Ref Finance pool (used by `--ref-finance-tracking`)
+    ref_finance_pools: HashMap<Vec<u8>, Vec<u8>>,
+    /// Per-shard totals since the last summary (used by `--shard-stats-summary-interval-blocks`)
+    shard_stats_summary: shard_stats::ShardStatsSummary,
+    /// Height of the last block we have received (used by `--verify-sequence`)
+    last_block_height: Option<u64>,
+    /// Token symbols and decimals (used by `--ft-token-list`)
+    ft_token_list: Option<ft::FtTokenList>,
+    /// The account history files and the DuckDB database
+    sink_writer: async_writes::SinkWriter,
+    /// Number of blocks we failed to handle (used by `--safe-mode`)
+    safe_mode_errors: u64,
+    /// Unvested amounts of the watched lockup accounts (used by `--lockup-tracking`)
+    lockup_tracker: lockup::LockupTracker,
+    /// Used by `--near-social-follow` and `--social-widget-tracking`
+    social_db: social::SocialDbDecoder,
+    /// Used by `--block-time-alert`
+    block_time_tracker: Option<block_time::BlockTimeTracker>,
+    /// Used by `--contract-size-alert-bytes`
+    contract_size_tracker: Option<contract_size::ContractSizeTracker>,
+    /// Number of shards in the previous block (used by `--sharding-aware`)
+    last_shard_count: Option<usize>,
+    /// The epoch of the previous block (used by `--validator-set-tracking`)
+    last_epoch_id: Option<CryptoHash>,
+    /// The active validators of the current epoch (used by `--validator-set-tracking`)
+    validators: Option<Vec<validator_set::ValidatorInfo>>,
+    /// Accounts watched in addition to `--accounts` (used by `--watch-validators`)
+    extra_watching_list: std::collections::HashSet<AccountId>,
+    /// Used by `--account-birth-tracking`
+    account_birth_tracker: Option<account_birth::AccountBirthTracker>,
+    /// Used by `--alert-pagerduty`
+    pagerduty: Option<pagerduty::PagerDuty>,
+    /// Used by `--alert-silence-window`
+    alert_silencer: Option<alert_silence::AlertSilencer>,
+    /// Used by `--receipt-delay-tracking`
+    receipt_delay_tracker: Option<receipt_delay::ReceiptDelayTracker>,
+    /// Used by `--storage-alert-bytes`
+    storage_alert_tracker: Option<storage_alert::StorageAlertTracker>,
+    /// Used by `--wasm-plugin`
+    wasm_plugin: Option<wasm_plugin::WasmPlugin>,
+    /// Passkeys of the FastAuth accounts (used by `--fastauth-tracking`)
+    fastauth_tracker: fastauth::FastAuthTracker,
+    /// Time spent in each step of the current block (used by `--developer-mode`)
+    dev_timing: dev_timing::DevTiming,
+    /// Last prices of the oracle feeds (used by `--oracle-price-deviation-alert`)
+    oracle_prices: oracle::OraclePriceTracker,
+    /// ERC-20 addresses of the bridged tokens (used by `--aurora-bridge-tracking`)
+    aurora_bridge: aurora_bridge::AuroraBridgeDecoder,
+    /// Number of blocks we have handled since the start
+    blocks_handled: u64,
+    /// Number of blocks we have printed the shard of (used by `--debug-shard`)
+    debug_shard_blocks: u64,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), tokio::io::Error> {
+async fn main() -> anyhow::Result<()> {
     // Read the args passed to the application from commmand-line
-    let opts: Opts = Opts::parse();
+    let opts: Opts = Opts::parse_args();
+    match &opts.command {
+        Command::GenerateCompletions { shell } => {
+            generate_completions(*shell);
+            return Ok(());
+        }
+        Command::DebugBlock { height, chain_id } => return debug_block(*height, chain_id).await,
+        Command::Chain(_) => {}
+    }
 
     // Inform about indexer is being started and what accounts we're watching for
     eprintln!(
@@ -33,60 +180,2009 @@ async fn main() -> Result<(), tokio::io::Error> {
         &opts.accounts
     );
 
+    if opts.ecosystem_stats {
+        eprintln!(
+            "Watching all the accounts for --ecosystem-stats, expect a lot more output \
+            (limit it with --ecosystem-stats-max-rate-limit)"
+        );
+    }
+
+    if opts.verify_checksums && opts.block_range_list.is_none() && !opts.use_index_file {
+        eprintln!(
+            "The NEAR Lake Framework fetches the streamed blocks itself, \
+            --verify-checksums only checks the blocks of --block-range-list, --use-index-file \
+            and the startup checks"
+        );
+    }
+
     // NEAR Lake Framework boilerplate
     // Instantiate the config
-    let mut config = LakeConfigBuilder::default().start_block_height(opts.block_height);
+    let block_list = opts
+        .block_range_list
+        .as_deref()
+        .map(block_range::BlockRangeIterator::load)
+        .transpose()?;
+    let start_block_height = match &block_list {
+        Some(block_list) => block_list
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("--block-range-list has no blocks"))?,
+        None => start_block_height(&opts).await?,
+    };
+    let config = opts.to_lake_config(start_block_height).await;
+
+    if opts.verify_genesis || opts.expected_genesis_hash.is_some() {
+        verify_genesis(&opts, &config.s3_bucket_name).await?;
+    }
+    if opts.startup_validation {
+        validate_startup(&opts, &config.s3_bucket_name, start_block_height).await?;
+    }
+
+    let presigner = match opts.presign_matched_blocks {
+        Some(expiry_seconds) => Some(
+            s3::Presigner::new(
+                &opts,
+                &config.s3_bucket_name,
+                std::time::Duration::from_secs(expiry_seconds),
+            )
+            .await?,
+        ),
+        None => None,
+    };
 
-    match opts.chain_id {
-        ChainId::Mainnet => config = config.mainnet(),
-        ChainId::Testnet => config = config.testnet(),
+    let block_index_reader = if opts.use_index_file {
+        let reader = lake_index::BlockIndexReader::new(
+            s3::client(&opts).await,
+            config.s3_bucket_name.clone(),
+        );
+        match reader.fetch(&opts.index_file_key, start_block_height).await {
+            Ok(heights) => {
+                eprintln!("The index file lists {} blocks to fetch", heights.len());
+                Some((reader, heights))
+            }
+            Err(err) => {
+                eprintln!(
+                    "Failed to read the index file {}, fetching all the blocks: {}",
+                    opts.index_file_key, err
+                );
+                None
+            }
+        }
+    } else {
+        None
     };
 
     // Instantiating the stream
-    let (_, mut stream) =
-        near_lake_framework::streamer(config.build().expect("Failed to build LakeConfig"));
+    let mut stream = match (block_list, block_index_reader, &opts.s3_object_cache) {
+        (Some(block_list), _, _) => block_range::block_list_stream(
+            s3::client(&opts).await,
+            config.s3_bucket_name.clone(),
+            block_list,
+            opts.verify_checksums,
+            opts.retry_policy(),
+        ),
+        (None, Some((reader, heights)), _) => {
+            reader.indexed_stream(heights, config, opts.verify_checksums, opts.retry_policy())
+        }
+        (None, None, Some(cache_dir)) => object_cache::cached_stream(
+            object_cache::ObjectCache::open(cache_dir.clone(), opts.s3_cache_max_size_mb)?,
+            config,
+        ),
+        (None, None, None) => near_lake_framework::streamer(config).1,
+    };
+
+    let denylist = opts.denylist()?;
+    if !denylist.is_empty() {
+        eprintln!(
+            "Excluding accounts matching the denylist: \n {:#?}",
+            denylist.patterns()
+        );
+    }
+
+    let watcher_config = WatcherConfig {
+        denylist,
+        aurora_abi: opts.aurora_abi()?,
+        presigner,
+        dao_accounts: opts.dao_accounts()?,
+        allowed_full_access_keys: opts.allowed_full_access_keys.iter().cloned().collect(),
+        alert_webhook: opts.alert_webhook.clone().map(webhook::Webhook::new),
+        slack: opts
+            .alert_slack_webhook
+            .clone()
+            .map(|url| slack::Slack::new(url, opts.slack_mention_user.clone())),
+        discord: opts.alert_discord_webhook.clone().map(|url| {
+            discord::Discord::new(
+                url,
+                opts.discord_role_mention.clone(),
+                opts.discord_embed_color_critical,
+            )
+        }),
+        abi_validator: opts
+            .abi_check
+            .as_deref()
+            .map(abi_check::NearAbiValidator::load)
+            .transpose()?,
+        validator_rpc: if opts.validator_set_tracking {
+            Some(rpc::JsonRpcClient::new(opts.rpc_url()?))
+        } else {
+            None
+        },
+        faucet: if opts.faucet_tracking {
+            let faucet_account = opts
+                .faucet_account
+                .clone()
+                .or_else(|| opts.chain_id().faucet_account())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("--faucet-account is required outside of testnet")
+                })?;
+            Some(faucet::FaucetDecoder::new(faucet_account))
+        } else {
+            None
+        },
+        tx_pattern: opts
+            .tx_pattern
+            .as_deref()
+            .map(|pattern| tx_pattern::TxPattern::parse(pattern, &opts.accounts))
+            .transpose()?,
+        aurora_tx: if opts.aurora_tx_tracking {
+            Some(aurora_tx::AuroraTxDecoder::new(
+                &opts.aurora_watch_evm_address,
+            )?)
+        } else {
+            None
+        },
+        hash_ring: opts
+            .consistent_hash_shard
+            .map(consistent_hash::HashRing::new),
+    };
+    let mut state = WatcherState {
+        dev_timing: dev_timing::DevTiming::new(opts.developer_mode)?,
+        ..WatcherState::default()
+    };
+    #[cfg(feature = "developer-mode")]
+    let _flame_guard = if opts.developer_mode {
+        Some(dev_timing::init_flame_layer()?)
+    } else {
+        None
+    };
+    let mut sinks = async_writes::Sinks::default();
+    if let Some(history_dir) = &opts.near_account_history {
+        sinks.account_history = Some(account_history::AccountHistoryWriter::new(
+            history_dir.clone(),
+            opts.account_history_max_size_mb,
+            opts.output_gzip_compress.then(|| opts.gzip_level),
+        )?);
+    }
+
+    state.block_time_tracker = opts.block_time_alert.map(block_time::BlockTimeTracker::new);
+    state.contract_size_tracker = opts
+        .contract_size_alert_bytes
+        .map(contract_size::ContractSizeTracker::new);
+    if opts.account_birth_tracking {
+        state.account_birth_tracker = Some(account_birth::AccountBirthTracker::load(
+            opts.account_birth_cache_file.clone(),
+        )?);
+    }
+    state.pagerduty = opts.alert_pagerduty.clone().map(|routing_key| {
+        pagerduty::PagerDuty::new(routing_key, opts.pagerduty_resolve_after_blocks)
+    });
+    state.alert_silencer = opts
+        .alert_silence_window
+        .map(alert_silence::AlertSilencer::new);
+    if opts.receipt_delay_tracking {
+        state.receipt_delay_tracker = Some(receipt_delay::ReceiptDelayTracker::new(
+            opts.max_receipt_delay_blocks,
+        ));
+    }
+    if let Some(storage_alert_bytes) = opts.storage_alert_bytes {
+        state.storage_alert_tracker = Some(storage_alert::StorageAlertTracker::new(
+            storage_alert_bytes,
+            opts.storage_alert_hysteresis_pct,
+        ));
+    }
+    if let Some(wasm_plugin) = &opts.wasm_plugin {
+        state.wasm_plugin = Some(wasm_plugin::WasmPlugin::load(
+            wasm_plugin,
+            opts.wasm_fuel_limit,
+        )?);
+    }
+    if opts.duckdb_path.is_some() || opts.duckdb_in_memory {
+        sinks.duckdb = Some(duckdb_sink::DuckDbSink::open(opts.duckdb_path.as_deref())?);
+    } else if opts.duckdb_query.is_some() {
+        anyhow::bail!("--duckdb-query needs either --duckdb-path or --duckdb-in-memory");
+    }
+    if opts.async_writes && opts.async_write_queue_depth == 0 {
+        anyhow::bail!("--async-write-queue-depth has to be at least 1");
+    }
+    state.sink_writer = if opts.async_writes {
+        async_writes::SinkWriter::new_async(
+            sinks,
+            opts.async_write_queue_depth,
+            opts.async_write_error_mode,
+        )
+    } else {
+        async_writes::SinkWriter::new(sinks)
+    };
+
+    if let Some(ft_token_list) = &opts.ft_token_list {
+        state.ft_token_list = Some(ft::FtTokenList::load(ft_token_list)?);
+    }
+    if opts.max_memory_mb.is_some() && memory_cap::resident_memory_mb().is_none() {
+        anyhow::bail!("--max-memory-mb needs /proc/self/status to read the memory usage");
+    }
+    let reload_requested = watch_sighup();
+
+    // We stop on Ctrl+C to write everything we have buffered
+    let mut shutdown = Box::pin(tokio::signal::ctrl_c());
+    let mut stream_error = None;
 
     // Finishing the boilerplate with a busy loop to actually handle the stream
-    while let Some(streamer_message) = stream.recv().await {
-        handle_streamer_message(streamer_message, &opts.accounts).await;
+    loop {
+        let streamer_message = {
+            let _receive_timer = state.dev_timing.start(dev_timing::Step::StreamReceive);
+            tokio::select! {
+                streamer_message = stream.recv() => match streamer_message {
+                    Some(streamer_message) => streamer_message,
+                    None => break,
+                },
+                _ = &mut shutdown => {
+                    eprintln!("Shutting down...");
+                    break;
+                }
+            }
+        };
+        if reload_requested.swap(false, std::sync::atomic::Ordering::Relaxed) {
+            if let Some(ft_token_list) = &opts.ft_token_list {
+                match ft::FtTokenList::load(ft_token_list) {
+                    Ok(token_list) => {
+                        eprintln!("Reloaded {}", ft_token_list.display());
+                        state.ft_token_list = Some(token_list);
+                    }
+                    Err(err) => eprintln!("Failed to reload the token list: {}", err),
+                }
+            }
+        }
+
+        // The errors stop the watcher, `--safe-mode` only skips the blocks that panic
+        let result = if opts.safe_mode {
+            // The state might be left half-updated by the failed block,
+            // we accept it in exchange for not stopping the watcher
+            let result = std::panic::AssertUnwindSafe(handle_streamer_message(
+                &streamer_message,
+                &opts,
+                &watcher_config,
+                &mut state,
+            ))
+            .catch_unwind()
+            .await;
+            result.unwrap_or_else(|panic| {
+                state.safe_mode_errors += 1;
+                log_safe_mode_error(&opts, &streamer_message, panic, state.safe_mode_errors);
+                Ok(())
+            })
+        } else {
+            handle_streamer_message(&streamer_message, &opts, &watcher_config, &mut state).await
+        };
+        if let Err(err) = result {
+            stream_error = Some(err);
+            break;
+        }
+
+        if let Some(max_memory_mb) = opts.max_memory_mb {
+            if let Some(resident_mb) = memory_cap::over_cap(max_memory_mb) {
+                if let Err(err) = state
+                    .sink_writer
+                    .write(async_writes::SinkWrite::Flush)
+                    .await
+                {
+                    stream_error = Some(err);
+                    break;
+                }
+                // The next change of each account is printed in full, as right after the start
+                state.last_account_views = HashMap::new();
+                let event = memory_cap::wait_for_release(
+                    resident_mb,
+                    max_memory_mb,
+                    streamer_message.block.header.height,
+                )
+                .await;
+                let mut event_json = serde_json::to_value(event)
+                    .expect("Failed to serialize MemoryPressureEvent to JSON");
+                if opts.async_writes {
+                    event_json["async_write_queue_depth"] = state.sink_writer.queue_depth().into();
+                }
+                print_event(
+                    streamer_message.block.header.height,
+                    "memory_pressure_event",
+                    &event_json,
+                );
+            }
+        }
+    }
+
+    // What we have buffered is written out even if the watcher has stopped on an error
+    let finished = finish(&opts, &mut state).await;
+    match stream_error {
+        Some(err) => {
+            if let Err(finish_err) = finished {
+                eprintln!("Failed to finish the writes: {}", finish_err);
+            }
+            Err(err)
+        }
+        None => finished,
+    }
+}
+
+/// Waits for the queued writes, flushes the sinks and saves the caches
+async fn finish(opts: &Opts, state: &mut WatcherState) -> anyhow::Result<()> {
+    let mut sinks = std::mem::take(&mut state.sink_writer).finish().await?;
+    if let Some(account_history) = &mut sinks.account_history {
+        account_history.flush()?;
+    }
+    if let Some(account_birth_tracker) = &state.account_birth_tracker {
+        account_birth_tracker.save()?;
+    }
+    if let (Some(duckdb), Some(duckdb_query)) = (&sinks.duckdb, &opts.duckdb_query) {
+        duckdb.print_query(duckdb_query)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the flag that is raised every time the watcher receives SIGHUP,
+/// so we know it's time to reload the files we have read at the start
+fn watch_sighup() -> std::sync::Arc<std::sync::atomic::AtomicBool> {
+    let reload_requested = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    #[cfg(unix)]
+    {
+        let reload_requested = reload_requested.clone();
+        tokio::spawn(async move {
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("Failed to listen to SIGHUP");
+            while hangup.recv().await.is_some() {
+                reload_requested.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        });
+    }
+    reload_requested
+}
+
+/// Reports the URL we failed to pre-sign, the change is still printed without it
+fn presigned_url(url: anyhow::Result<String>) -> Option<String> {
+    match url {
+        Ok(url) => Some(url),
+        Err(err) => {
+            eprintln!("Failed to pre-sign the block URL: {}", err);
+            None
+        }
+    }
+}
+
+/// Reports the block we failed to handle in `--safe-mode`
+/// and appends it to the error log if one is set
+fn log_safe_mode_error(
+    opts: &Opts,
+    streamer_message: &near_lake_framework::near_indexer_primitives::StreamerMessage,
+    panic: Box<dyn std::any::Any + Send>,
+    safe_mode_errors: u64,
+) {
+    let error = panic
+        .downcast_ref::<&str>()
+        .map(|error| error.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown error".to_string());
+    eprintln!(
+        "Failed to handle block #{}: {}. Skipping it (safe_mode_errors_total: {})",
+        streamer_message.block.header.height, error, safe_mode_errors
+    );
+
+    if let Some(safe_mode_error_log) = &opts.safe_mode_error_log {
+        let error_json = serde_json::json!({
+            "block_height": streamer_message.block.header.height,
+            "error": error,
+            "streamer_message": streamer_message,
+        });
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(safe_mode_error_log)
+            .and_then(|mut file| writeln!(file, "{}", error_json));
+        if let Err(err) = written {
+            eprintln!(
+                "Failed to write to {}: {}",
+                safe_mode_error_log.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Prints the completions script to stdout and the hint
+/// on how to install it to stderr
+fn generate_completions(shell: clap_complete::Shell) {
+    let bin_name = "near-lake-accounts-watcher";
+    clap_complete::generate(
+        shell,
+        &mut Opts::command(),
+        bin_name,
+        &mut std::io::stdout(),
+    );
+
+    let install_hint = match shell {
+        clap_complete::Shell::Bash => format!(
+            "{} generate-completions bash > /etc/bash_completion.d/{}",
+            bin_name, bin_name
+        ),
+        clap_complete::Shell::Zsh => format!(
+            "{} generate-completions zsh > \"${{fpath[1]}}/_{}\"",
+            bin_name, bin_name
+        ),
+        clap_complete::Shell::Fish => format!(
+            "{} generate-completions fish > ~/.config/fish/completions/{}.fish",
+            bin_name, bin_name
+        ),
+        clap_complete::Shell::PowerShell => {
+            format!("{} generate-completions powershell >> $PROFILE", bin_name)
+        }
+        _ => format!(
+            "{} generate-completions {} and follow your shell's documentation",
+            bin_name, shell
+        ),
+    };
+    eprintln!("To install the completions run:\n    {}", install_hint);
+}
+
+/// Resolves `--start-from-genesis`, `--start-from-epoch` and `--time-travel`
+/// into the block height
+async fn start_block_height(opts: &Opts) -> anyhow::Result<u64> {
+    if opts.start_from_genesis {
+        if matches!(opts.chain_id(), ChainId::Mainnet) {
+            eprintln!("Starting from the genesis of mainnet, it will take a very long time");
+        }
+        // The custom networks are expected to start from the very first block
+        return Ok(opts
+            .chain_id()
+            .genesis()
+            .map(|(genesis_height, _)| genesis_height)
+            .unwrap_or(0));
+    }
+    if let Some(epoch_height) = opts.start_from_epoch {
+        let rpc = rpc::JsonRpcClient::new(opts.rpc_url()?);
+        let height = rpc::find_epoch_start_height(&rpc, epoch_height).await?;
+        eprintln!("Epoch {} starts at block #{}", epoch_height, height);
+        return Ok(height);
     }
+    if let Some(time) = &opts.time_travel {
+        let target_ts = time
+            .timestamp_nanos_opt()
+            .and_then(|target_ts| u64::try_from(target_ts).ok())
+            .ok_or_else(|| anyhow::anyhow!("--time-travel {} is out of range", time))?;
+        let height = rpc::find_block_by_timestamp(opts.rpc_url()?, target_ts).await?;
+        eprintln!("The first block at or after {} is #{}", time, height);
+        return Ok(height);
+    }
+    Ok(opts
+        .block_height
+        .expect("clap requires --block-height without the other start options"))
+}
+
+/// Makes sure the bucket and the alert webhook are reachable before we start,
+/// so a misconfigured watcher doesn't run silently broken for hours
+async fn validate_startup(
+    opts: &Opts,
+    s3_bucket_name: &str,
+    start_block_height: u64,
+) -> anyhow::Result<()> {
+    let s3_client = s3::client(opts).await;
+    let height = s3::first_block_height_from(&s3_client, s3_bucket_name, start_block_height)
+        .await
+        .map_err(|err| anyhow::anyhow!("Failed to list the blocks in {}: {}", s3_bucket_name, err))?
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "There are no blocks from #{} in {}",
+                start_block_height,
+                s3_bucket_name
+            )
+        })?;
+    s3::fetch_block(&s3_client, s3_bucket_name, height, opts.verify_checksums)
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to fetch block #{} from {}: {}",
+                height,
+                s3_bucket_name,
+                err
+            )
+        })?;
+    eprintln!(
+        "Startup check: fetched block #{} from {}",
+        height, s3_bucket_name
+    );
 
+    if let Some(alert_webhook) = &opts.alert_webhook {
+        webhook::Webhook::new(alert_webhook.clone())
+            .ping()
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to ping the alert webhook: {}", err))?;
+        eprintln!("Startup check: the alert webhook has accepted the ping");
+    }
     Ok(())
 }
 
+/// Fetches the genesis block of the chain we expect from the bucket
+/// and compares its hash with the expected one. This way we don't accidentally
+/// watch the testnet data while we think it's mainnet (or vice versa)
+async fn verify_genesis(opts: &Opts, s3_bucket_name: &str) -> anyhow::Result<()> {
+    let (genesis_height, known_genesis_hash) = opts.chain_id().genesis().ok_or_else(|| {
+        anyhow::anyhow!("The genesis block of a custom NEAR Lake deployment is unknown")
+    })?;
+    let expected_genesis_hash = match &opts.expected_genesis_hash {
+        Some(hash) => hash
+            .parse()
+            .map_err(|err| anyhow::anyhow!("Invalid --expected-genesis-hash {}: {}", hash, err))?,
+        None => known_genesis_hash,
+    };
+
+    let s3_client = s3::client(opts).await;
+    let genesis_block = s3::fetch_block(
+        &s3_client,
+        s3_bucket_name,
+        genesis_height,
+        opts.verify_checksums,
+    )
+    .await
+    .map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to fetch genesis block #{} from {}: {}",
+            genesis_height,
+            s3_bucket_name,
+            err
+        )
+    })?;
+
+    eprintln!(
+        "Detected genesis block #{} with hash {}",
+        genesis_height, genesis_block.header.hash
+    );
+
+    if genesis_block.header.hash != expected_genesis_hash {
+        anyhow::bail!(
+            "Genesis hash mismatch: expected {}, got {}. Are you watching the right chain?",
+            expected_genesis_hash,
+            genesis_block.header.hash
+        );
+    }
+    Ok(())
+}
+
+/// Fetches the block and its shards straight from the bucket
+/// and prints every state change, watched or not
+async fn debug_block(height: u64, chain_id: &ChainId) -> anyhow::Result<()> {
+    let s3_client = s3::default_client(chain_id).await;
+    let s3_bucket_name = chain_id.s3_bucket_name();
+    let block = s3::fetch_block(&s3_client, s3_bucket_name, height, false)
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to fetch block #{} from {}: {}",
+                height,
+                s3_bucket_name,
+                err
+            )
+        })?;
+    eprintln!(
+        "Block #{} {} with {} chunks",
+        height,
+        block.header.hash,
+        block.chunks.len()
+    );
+
+    for chunk in &block.chunks {
+        let shard = s3::fetch_shard(&s3_client, s3_bucket_name, height, chunk.shard_id, false)
+            .await
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "Failed to fetch shard {} of block #{}: {}",
+                    chunk.shard_id,
+                    height,
+                    err
+                )
+            })?;
+        eprintln!(
+            "Shard {}: {} state changes",
+            shard.shard_id,
+            shard.state_changes.len()
+        );
+        for state_change in &shard.state_changes {
+            let mut changes_json = serde_json::to_value(state_change)
+                .expect("Failed to serialize StateChange to JSON");
+            changes_json["shard_id"] = shard.shard_id.into();
+            println!("#{}. {}", height, changes_json["type"]);
+            println!("{:#?}", changes_json);
+        }
+    }
+    Ok(())
+}
+
+/// Prints all the state changes of the shard the same way `debug-block` does,
+/// until `--debug-shard-max-blocks` blocks are printed
+fn print_debug_shard(
+    streamer_message: &near_lake_framework::near_indexer_primitives::StreamerMessage,
+    debug_shard: u64,
+    opts: &Opts,
+    state: &mut WatcherState,
+) {
+    if opts
+        .debug_shard_max_blocks
+        .map_or(false, |max_blocks| state.debug_shard_blocks >= max_blocks)
+    {
+        return;
+    }
+    let height = streamer_message.block.header.height;
+    let shard = match streamer_message
+        .shards
+        .iter()
+        .find(|shard| shard.shard_id == debug_shard)
+    {
+        Some(shard) => shard,
+        None => return,
+    };
+    for state_change in &shard.state_changes {
+        let mut changes_json =
+            serde_json::to_value(state_change).expect("Failed to serialize StateChange to JSON");
+        changes_json["shard_id"] = shard.shard_id.into();
+        println!("#{}. debug {}", height, changes_json["type"]);
+        println!("{:#?}", changes_json);
+    }
+    state.debug_shard_blocks += 1;
+    if opts.debug_shard_max_blocks == Some(state.debug_shard_blocks) {
+        eprintln!(
+            "Printed shard {} of {} blocks, --debug-shard is off now",
+            debug_shard, state.debug_shard_blocks
+        );
+    }
+}
+
 /// Function that receives the StreamerMessage from
-/// the NEAR Lake Framework and our list of watched
-/// account names so we know what we are looking for
-/// in each block.
+/// the NEAR Lake Framework and our options (including the list
+/// of watched account names) along with the things prepared from them
+/// so we know what we are looking for in each block.
 async fn handle_streamer_message(
-    streamer_message: near_lake_framework::near_indexer_primitives::StreamerMessage,
-    watching_list: &[AccountId],
-) {
+    streamer_message: &near_lake_framework::near_indexer_primitives::StreamerMessage,
+    opts: &Opts,
+    watcher_config: &WatcherConfig,
+    state: &mut WatcherState,
+) -> anyhow::Result<()> {
+    let watching_list = &opts.accounts;
+    let denylist = &watcher_config.denylist;
+    if opts.verify_sequence {
+        verify_sequence(&streamer_message.block.header, opts, state)?;
+    }
+    if let Some(block_time_tracker) = &mut state.block_time_tracker {
+        if let Some(alert) = block_time_tracker.check(
+            streamer_message.block.header.height,
+            streamer_message.block.header.timestamp_nanosec,
+        ) {
+            print_event(
+                streamer_message.block.header.height,
+                "block_time_anomaly",
+                &alert,
+            );
+        }
+    }
+    if opts.sharding_aware {
+        let shard_count = streamer_message.shards.len();
+        if let Some(last_shard_count) = state.last_shard_count.replace(shard_count) {
+            if last_shard_count != shard_count {
+                print_event(
+                    streamer_message.block.header.height,
+                    "resharding_event",
+                    &serde_json::json!({
+                        "old_shard_count": last_shard_count,
+                        "new_shard_count": shard_count,
+                        "block_height": streamer_message.block.header.height,
+                    }),
+                );
+            }
+        }
+    }
+    if let Some(validator_rpc) = &watcher_config.validator_rpc {
+        let epoch_id = streamer_message.block.header.epoch_id;
+        if state.last_epoch_id.replace(epoch_id) != Some(epoch_id) {
+            track_validator_set(
+                validator_rpc,
+                &epoch_id,
+                streamer_message.block.header.height,
+                opts,
+                state,
+            )
+            .await;
+        }
+    }
+    if let Some(debug_shard) = opts.debug_shard {
+        print_debug_shard(streamer_message, debug_shard, opts, state);
+    }
+    if let Some(protocol_version) = opts.protocol_version_filter {
+        if streamer_message.block.header.latest_protocol_version != protocol_version {
+            return Ok(());
+        }
+    }
+    track_receipt_delays(streamer_message, opts, watcher_config, state);
+    let block_index = block_index::BlockIndex::new(streamer_message);
+    let social_db_account = opts.chain_id().social_db_account();
+    let chain_signatures_account = opts.chain_id().chain_signatures_account();
     // StateChanges we are looking for can be found in each shard, so we iterate over available shards
-    for shard in streamer_message.shards {
-        for state_change in shard.state_changes {
+    let mut block_shard_stats = vec![];
+    // Pre-signed only once we have a match (used by `--presign-matched-blocks`)
+    let mut raw_block_url = None;
+    let dev_timing = &state.dev_timing;
+    if let Some(tx_pattern) = &watcher_config.tx_pattern {
+        // The pattern may span the receipts of several shards
+        let receipts: Vec<tx_pattern::ExecutedReceipt> = streamer_message
+            .shards
+            .iter()
+            .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+            .map(tx_pattern::ExecutedReceipt::from_outcome)
+            .collect();
+        if tx_pattern.matches(&receipts) {
+            let receipts: Vec<&tx_pattern::ExecutedReceipt> = receipts
+                .iter()
+                .filter(|receipt| tx_pattern.is_involved(receipt))
+                .collect();
+            print_event(
+                streamer_message.block.header.height,
+                "pattern_matched",
+                &serde_json::json!({
+                    "pattern": tx_pattern,
+                    "receipts": receipts,
+                }),
+            );
+        }
+    }
+    let include_fields: Vec<&str> = opts.include_fields.iter().map(String::as_str).collect();
+    let exclude_fields: Vec<&str> = opts.exclude_fields.iter().map(String::as_str).collect();
+    let mut ecosystem_stats = opts
+        .ecosystem_stats
+        .then(|| ecosystem_stats::EcosystemStats::new(streamer_message.block.header.height));
+    let mut printed_changes = 0;
+    // Changes of each account in the block (used by `--summarize-by-account`)
+    let mut account_summaries: BTreeMap<AccountId, Vec<serde_json::Value>> = BTreeMap::new();
+    // Stays empty with the natural order, the changes are printed right away then
+    let mut block_events = event_ordering::BlockEvents::default();
+    for shard in &streamer_message.shards {
+        let _shard_timer = dev_timing.start(dev_timing::Step::ShardIteration);
+        let shard_started_at = tokio::time::Instant::now();
+        let mut matched_changes = 0;
+        let mut raw_shard_url = None;
+
+        if opts.aurora_evm_events
+            && watching_list
+                .iter()
+                .any(|account_id| account_id.as_ref() == aurora::AURORA_ACCOUNT)
+        {
+            for outcome in &shard.receipt_execution_outcomes {
+                for event in aurora::evm_events(outcome, watcher_config.aurora_abi.as_ref()) {
+                    let mut event_json = serde_json::to_value(event)
+                        .expect("Failed to serialize AuroraEvmEvent to JSON");
+                    event_json["receipt_id"] = outcome.receipt.receipt_id.to_string().into();
+                    print_event(
+                        streamer_message.block.header.height,
+                        "aurora_evm_event",
+                        &event_json,
+                    );
+                }
+            }
+        }
+
+        if let Some(aurora_tx) = &watcher_config.aurora_tx {
+            if watching_list
+                .iter()
+                .any(|account_id| account_id.as_ref() == aurora::AURORA_ACCOUNT)
+            {
+                for outcome in &shard.receipt_execution_outcomes {
+                    for tx in aurora_tx.transactions(outcome, streamer_message.block.header.height)
+                    {
+                        print_event(streamer_message.block.header.height, "aurora_evm_tx", &tx);
+                    }
+                }
+            }
+        }
+
+        let is_aurora_bridge_tracked = opts.aurora_bridge_tracking
+            && watching_list
+                .iter()
+                .any(|account_id| account_id.as_ref() == aurora::AURORA_ACCOUNT);
+        if is_aurora_bridge_tracked {
+            for outcome in &shard.receipt_execution_outcomes {
+                for transfer in ft::ft_transfer_events(outcome, None) {
+                    if let Some(event) = state
+                        .aurora_bridge
+                        .decode_transfer(&transfer, streamer_message.block.header.height)
+                    {
+                        print_event(
+                            streamer_message.block.header.height,
+                            event.event_type,
+                            &event,
+                        );
+                    }
+                }
+            }
+        }
+
+        if opts.watch_ft_transfers {
+            for outcome in &shard.receipt_execution_outcomes {
+                for event in ft::ft_transfer_events(outcome, state.ft_token_list.as_ref()) {
+                    let is_watched = watching_list.iter().any(|account_id| {
+                        account_id.as_ref() == event.old_owner_id
+                            || account_id.as_ref() == event.new_owner_id
+                    });
+                    if is_watched {
+                        print_event(streamer_message.block.header.height, "ft_transfer", &event);
+                    }
+                }
+            }
+        }
+
+        if opts.chain_sig_tracking {
+            for outcome in &shard.receipt_execution_outcomes {
+                if Some(&outcome.receipt.receiver_id) == chain_signatures_account.as_ref() {
+                    for (event_type, event) in chain_signatures::chain_signature_events(
+                        outcome,
+                        streamer_message.block.header.height,
+                    ) {
+                        print_event(streamer_message.block.header.height, event_type, &event);
+                    }
+                }
+            }
+        }
+
+        if let Some(ecosystem_stats) = &mut ecosystem_stats {
+            for outcome in &shard.receipt_execution_outcomes {
+                ecosystem_stats.add_receipt(outcome);
+            }
+        }
+
+        if let Some(abi_validator) = &watcher_config.abi_validator {
+            for outcome in &shard.receipt_execution_outcomes {
+                if !watching_list.contains(&outcome.receipt.receiver_id) {
+                    continue;
+                }
+                for failure in
+                    abi_validator.validation_failures(outcome, streamer_message.block.header.height)
+                {
+                    print_event(
+                        streamer_message.block.header.height,
+                        "abi_validation_failure",
+                        &failure,
+                    );
+                }
+            }
+        }
+
+        if let Some(faucet) = &watcher_config.faucet {
+            for outcome in &shard.receipt_execution_outcomes {
+                if let Some(event) = faucet.detect_distribution(outcome, streamer_message) {
+                    print_event(
+                        streamer_message.block.header.height,
+                        "faucet_distribution",
+                        &event,
+                    );
+                }
+            }
+        }
+
+        if opts.burrow_tracking {
+            for outcome in &shard.receipt_execution_outcomes {
+                for (event_type, event) in burrow::position_events(outcome) {
+                    print_event(streamer_message.block.header.height, event_type, &event);
+                }
+            }
+        }
+
+        if opts.near_social_follow || opts.social_widget_tracking {
+            for state_change in &shard.state_changes {
+                if Some(state_change_account_id(state_change)) == social_db_account.as_ref() {
+                    state.social_db.learn(&state_change.value);
+                }
+            }
+        }
+
+        for state_change in &shard.state_changes {
+            if let Some(ecosystem_stats) = &mut ecosystem_stats {
+                ecosystem_stats
+                    .add_state_change(state_change_account_id(state_change), &state_change.value);
+            }
+
+            // Near.Social follows are stored in the Near.Social contract,
+            // so we look for them regardless of the watching list
+            if opts.near_social_follow
+                && Some(state_change_account_id(state_change)) == social_db_account.as_ref()
+            {
+                if let Some((event_type, event)) = social::follow_event(
+                    &state.social_db,
+                    &state_change.value,
+                    streamer_message.block.header.height,
+                ) {
+                    if watching_list.contains(&event.followed) {
+                        print_event(streamer_message.block.header.height, event_type, &event);
+                    }
+                }
+            }
+
+            if opts.social_widget_tracking
+                && Some(state_change_account_id(state_change)) == social_db_account.as_ref()
+            {
+                if let Some(event) = social::SocialWidgetDecoder::new(&state.social_db)
+                    .widget_event(&state_change.value, streamer_message.block.header.height)
+                {
+                    if opts.track_widget_author.is_empty()
+                        || opts.track_widget_author.contains(&event.author)
+                    {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "widget_deployed",
+                            &event,
+                        );
+                    }
+                }
+            }
+
+            if opts.near_ibc_tracking
+                && opts.ibc_contract_account.as_ref() == Some(state_change_account_id(state_change))
+            {
+                if let Some(event) = ibc::ibc_event(&state_change.value) {
+                    let mut event_json =
+                        serde_json::to_value(&event).expect("Failed to serialize IbcEvent to JSON");
+                    event_json["block_height"] = streamer_message.block.header.height.into();
+                    print_event(
+                        streamer_message.block.header.height,
+                        event.kind.event_type(),
+                        &event_json,
+                    );
+                }
+            }
+
+            if opts.fastauth_tracking {
+                let fastauth_authenticator = opts
+                    .fastauth_authenticator
+                    .as_ref()
+                    .expect("--fastauth-tracking requires --fastauth-authenticator");
+                if let Some(mut event) = state
+                    .fastauth_tracker
+                    .check(state_change, fastauth_authenticator)
+                {
+                    event.relayer = block_index
+                        .cause_context(&state_change.cause)
+                        .map(|cause_context| cause_context.sender_id().clone());
+                    print_event(
+                        streamer_message.block.header.height,
+                        event.event_type,
+                        &event,
+                    );
+                }
+            }
+
+            if opts.lockup_tracking {
+                if let StateChangeValueView::DataUpdate {
+                    account_id,
+                    key,
+                    value,
+                } = &state_change.value
+                {
+                    if lockup::LockupTracker::is_lockup_account(account_id)
+                        && is_change_watched(
+                            state_change,
+                            watching_list,
+                            &state.extra_watching_list,
+                            denylist,
+                            false,
+                            watcher_config.hash_ring.as_ref(),
+                        )
+                    {
+                        if let Some(event) = state.lockup_tracker.check(
+                            account_id,
+                            key.as_ref(),
+                            value.as_ref(),
+                            streamer_message.block.header.timestamp_nanosec,
+                            streamer_message.block.header.height,
+                        ) {
+                            print_event(
+                                streamer_message.block.header.height,
+                                "lockup_vesting_event",
+                                &event,
+                            );
+                        }
+                    }
+                }
+            }
+
+            if opts.staking_pool_tracking {
+                if let (
+                    StateChangeValueView::DataUpdate {
+                        account_id,
+                        key,
+                        value,
+                    },
+                    StateChangeCauseView::ReceiptProcessing { receipt_hash },
+                ) = (&state_change.value, &state_change.cause)
+                {
+                    let (key, value): (&[u8], &[u8]) = (key.as_ref(), value.as_ref());
+                    if watching_list.contains(account_id)
+                        && staking_pool::StakingPoolDecoder::is_staking_pool(account_id)
+                    {
+                        if let (Some(total_stake_shares), Some(outcome)) = (
+                            staking_pool::StakingPoolDecoder::total_stake_shares(key, value),
+                            block_index.receipts.get(receipt_hash),
+                        ) {
+                            for log in &outcome.execution_outcome.outcome.logs {
+                                if let Some(event) = staking_pool::StakingPoolDecoder::decode_log(
+                                    log,
+                                    account_id,
+                                    total_stake_shares,
+                                    streamer_message.block.header.height,
+                                ) {
+                                    print_event(
+                                        streamer_message.block.header.height,
+                                        event.event_type,
+                                        &event,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            if is_aurora_bridge_tracked {
+                if let StateChangeValueView::DataUpdate {
+                    account_id,
+                    key,
+                    value,
+                } = &state_change.value
+                {
+                    if account_id.as_ref() == aurora::AURORA_ACCOUNT {
+                        state
+                            .aurora_bridge
+                            .record_mapping(key.as_ref(), value.as_ref());
+                    }
+                }
+            }
+
+            if opts.dao_tracking {
+                if let StateChangeValueView::DataUpdate {
+                    account_id,
+                    key,
+                    value,
+                } = &state_change.value
+                {
+                    if astro_dao::AstroDaoDecoder::is_dao_account(account_id)
+                        || watcher_config.dao_accounts.contains(account_id)
+                    {
+                        if let Some(event) =
+                            astro_dao::AstroDaoDecoder::decode(key.as_ref(), value.as_ref())
+                        {
+                            let mut event_json = serde_json::to_value(&event)
+                                .expect("Failed to serialize DaoEvent to JSON");
+                            event_json["dao_id"] = account_id.to_string().into();
+                            print_event(
+                                streamer_message.block.header.height,
+                                event.event_type,
+                                &event_json,
+                            );
+                        }
+                    }
+                }
+            }
+
+            if opts.oracle_tracking {
+                if let StateChangeValueView::DataUpdate {
+                    account_id,
+                    key,
+                    value,
+                } = &state_change.value
+                {
+                    let (key, value): (&[u8], &[u8]) = (key.as_ref(), value.as_ref());
+                    let price_update = if watching_list.contains(account_id) {
+                        oracle::OracleDecoder::decode_price_key_value(key, value)
+                    } else {
+                        None
+                    };
+                    if let Some(price_update) = price_update {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "oracle_price_update",
+                            &oracle::OraclePriceEvent {
+                                oracle_id: account_id.clone(),
+                                asset_id: price_update.asset_id.clone(),
+                                price_usd: price_update.price_usd,
+                                block_height: streamer_message.block.header.height,
+                                block_timestamp: price_update.block_timestamp,
+                            },
+                        );
+                        if let Some(max_deviation_pct) = opts.oracle_price_deviation_alert {
+                            if let Some(alert) = state.oracle_prices.check(
+                                account_id,
+                                &price_update,
+                                max_deviation_pct,
+                                streamer_message.block.header.height,
+                            ) {
+                                print_event(
+                                    streamer_message.block.header.height,
+                                    "oracle_price_deviation_alert",
+                                    &alert,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+
+            if opts.ref_finance_tracking {
+                if let StateChangeValueView::DataUpdate {
+                    account_id,
+                    key,
+                    value,
+                } = &state_change.value
+                {
+                    let (key, value): (&[u8], &[u8]) = (key.as_ref(), value.as_ref());
+                    if account_id.as_ref() == ref_finance::REF_FINANCE_ACCOUNT
+                        && ref_finance::RefFinanceDecoder::pool_id(key).is_some()
+                    {
+                        // We need the previous value of the pool to find out what has changed
+                        let old = state.ref_finance_pools.insert(key.to_vec(), value.to_vec());
+                        if let Some(event) = ref_finance::RefFinanceDecoder::decode_pool_update(
+                            key,
+                            old.as_deref(),
+                            value,
+                        ) {
+                            print_event(
+                                streamer_message.block.header.height,
+                                "ref_pool_swap",
+                                &event,
+                            );
+                        }
+                    }
+                }
+            }
+
             // We want to print the block height and
             // change type if the StateChange affects one of the accounts we are watching for
-            if is_change_watched(&state_change, watching_list) {
+            let is_watched = {
+                let _matching_timer = dev_timing.start(dev_timing::Step::Matching);
+                is_change_watched(
+                    state_change,
+                    watching_list,
+                    &state.extra_watching_list,
+                    denylist,
+                    opts.ecosystem_stats,
+                    watcher_config.hash_ring.as_ref(),
+                )
+            };
+            if is_watched {
+                matched_changes += 1;
+                if let Some(max_rate) = opts.ecosystem_stats_max_rate_limit {
+                    if printed_changes >= max_rate {
+                        if let Some(ecosystem_stats) = &mut ecosystem_stats {
+                            ecosystem_stats.dropped_changes += 1;
+                        }
+                        continue;
+                    }
+                }
+                printed_changes += 1;
                 // We convert it to JSON in order to show it is possible
                 // also, it is easier to read the printed version for this tutorial
                 // but we don't encourage you to do the same in your indexer. It's up to you
-                let changes_json = serde_json::to_value(state_change)
-                    .expect("Failed to serialize StateChange to JSON");
-                println!(
-                    "#{}. {}",
-                    streamer_message.block.header.height, changes_json["type"]
+                let mut changes_json = {
+                    let _serialization_timer = dev_timing.start(dev_timing::Step::Serialization);
+                    serde_json::to_value(state_change)
+                        .expect("Failed to serialize StateChange to JSON")
+                };
+                // The plugin goes first, so the changes it drops or rewrites are dropped
+                // or rewritten everywhere: in the sinks, the alerts and every output shape
+                let mut plugged_change = None;
+                if let Some(wasm_plugin) = &mut state.wasm_plugin {
+                    match wasm_plugin.apply(&changes_json) {
+                        Ok(Some(transformed)) => {
+                            plugged_change = Some(
+                                serde_json::from_value::<StateChangeWithCauseView>(
+                                    transformed.clone(),
+                                )
+                                .ok(),
+                            );
+                            changes_json = transformed;
+                        }
+                        Ok(None) => continue,
+                        // We'd rather print the change as is than lose it
+                        Err(err) => eprintln!(
+                            "WASM plugin failed on #{}: {}",
+                            streamer_message.block.header.height, err
+                        ),
+                    }
+                }
+                // The wallet and delta shapes are built from the typed change, they can't be
+                // if the plugin has turned the change into something else
+                let is_typed = !matches!(plugged_change, Some(None));
+                let state_change = match &plugged_change {
+                    Some(Some(plugged_change)) => plugged_change,
+                    _ => state_change,
+                };
+                if state.sink_writer.has_sinks() {
+                    let _sink_timer = dev_timing.start(dev_timing::Step::SinkWrite);
+                    let change = changes_json.clone();
+                    let mut event_json = change.clone();
+                    event_json["block_height"] = streamer_message.block.header.height.into();
+                    state
+                        .sink_writer
+                        .write(async_writes::SinkWrite::AccountHistory {
+                            account_id: state_change_account_id(state_change).clone(),
+                            block_height: streamer_message.block.header.height,
+                            event: event_json,
+                        })
+                        .await?;
+                    state
+                        .sink_writer
+                        .write(async_writes::SinkWrite::DuckDb {
+                            block_height: streamer_message.block.header.height,
+                            block_hash: streamer_message.block.header.hash,
+                            shard_id: shard.shard_id,
+                            account_id: state_change_account_id(state_change).clone(),
+                            change,
+                        })
+                        .await?;
+                }
+                if opts.change_frequency_report.is_some() {
+                    state
+                        .change_frequency
+                        .record(state_change_account_id(state_change));
+                }
+                let change_type = change_type(&state_change.value);
+                if opts.namespace_stats {
+                    state
+                        .namespace_stats
+                        .record(state_change_account_id(state_change), change_type);
+                }
+                let is_critical = opts
+                    .critical_change_types
+                    .iter()
+                    .any(|critical_change_type| critical_change_type == change_type);
+                if is_critical || watcher_config.slack.is_some() || watcher_config.discord.is_some()
+                {
+                    if is_critical {
+                        send_critical_alert(
+                            streamer_message.block.header.height,
+                            state_change_account_id(state_change),
+                            change_type,
+                            &changes_json,
+                            watcher_config,
+                            &mut state.pagerduty,
+                            state.alert_silencer.as_mut(),
+                        );
+                    } else if !state
+                        .alert_silencer
+                        .as_mut()
+                        .map_or(false, |alert_silencer| {
+                            alert_silencer
+                                .is_silenced(state_change_account_id(state_change), change_type)
+                        })
+                    {
+                        if let Some(slack) = &watcher_config.slack {
+                            slack.send(
+                                streamer_message.block.header.height,
+                                state_change_account_id(state_change),
+                                change_type,
+                                &changes_json,
+                                false,
+                            );
+                        }
+                        if let Some(discord) = &watcher_config.discord {
+                            discord.send(
+                                streamer_message.block.header.height,
+                                state_change_account_id(state_change),
+                                change_type,
+                                &changes_json,
+                                false,
+                            );
+                        }
+                    }
+                }
+                if opts.keystore_watch {
+                    if let Some(alert) = keystore::full_access_key_alert(
+                        &state_change.value,
+                        &state_change.cause,
+                        &watcher_config.allowed_full_access_keys,
+                        streamer_message.block.header.height,
+                    ) {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "keystore_alert",
+                            &alert,
+                        );
+                        if alert.is_critical() {
+                            let alert_json = serde_json::to_value(&alert)
+                                .expect("Failed to serialize KeystoreAlert to JSON");
+                            send_critical_alert(
+                                streamer_message.block.header.height,
+                                &alert.account_id,
+                                "keystore_alert",
+                                &alert_json,
+                                watcher_config,
+                                &mut state.pagerduty,
+                                // Every unauthorized key is sent regardless of
+                                // the other alert filtering, even within the silence window
+                                None,
+                            );
+                        }
+                    }
+                }
+                if opts.near_transfer_detection {
+                    if let (
+                        StateChangeValueView::AccountUpdate { account_id, .. },
+                        StateChangeCauseView::ReceiptProcessing { receipt_hash },
+                    ) = (&state_change.value, &state_change.cause)
+                    {
+                        // The sender's balance changes are caused by the receipt too
+                        if let Some(outcome) = block_index
+                            .receipts
+                            .get(receipt_hash)
+                            .filter(|outcome| outcome.receipt.receiver_id == *account_id)
+                        {
+                            for event in near_transfer::near_transfer_events(
+                                outcome,
+                                streamer_message.block.header.height,
+                            ) {
+                                print_event(
+                                    streamer_message.block.header.height,
+                                    "near_transfer",
+                                    &event,
+                                );
+                            }
+                        }
+                    }
+                }
+                if let (
+                    Some(account_birth_tracker),
+                    StateChangeValueView::AccountUpdate { account_id, .. },
+                ) = (&mut state.account_birth_tracker, &state_change.value)
+                {
+                    if account_birth_tracker.is_first_seen(account_id) {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "account_first_seen",
+                            &serde_json::json!({
+                                "account_id": account_id,
+                                "block_height": streamer_message.block.header.height,
+                            }),
+                        );
+                    }
+                }
+                if let (
+                    Some(contract_size_tracker),
+                    StateChangeValueView::ContractCodeUpdate { account_id, code },
+                ) = (&mut state.contract_size_tracker, &state_change.value)
+                {
+                    if let Some(alert) = contract_size_tracker.check(
+                        account_id,
+                        code,
+                        streamer_message.block.header.height,
+                    ) {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "contract_size_alert",
+                            &alert,
+                        );
+                    }
+                }
+                if let (
+                    Some(storage_alert_tracker),
+                    StateChangeValueView::AccountUpdate {
+                        account_id,
+                        account,
+                    },
+                ) = (&mut state.storage_alert_tracker, &state_change.value)
+                {
+                    if let Some(event) = storage_alert_tracker.check(
+                        account_id,
+                        account.storage_usage,
+                        streamer_message.block.header.height,
+                    ) {
+                        print_event(
+                            streamer_message.block.header.height,
+                            "storage_limit_alert",
+                            &event,
+                        );
+                    }
+                }
+
+                // Additional fields we add to the printed change
+                let mut extra_fields = serde_json::Map::new();
+                if opts.resolve_tx_hash {
+                    if let Some(receipt_hash) = block_index::cause_receipt_hash(&state_change.cause)
+                    {
+                        match block_index.originating_transactions.get(receipt_hash) {
+                            Some(tx_hash) => {
+                                extra_fields.insert(
+                                    "originating_tx_hash".to_string(),
+                                    tx_hash.to_string().into(),
+                                );
+                            }
+                            // The receipt was produced in one of the previous blocks
+                            None => {
+                                extra_fields.insert(
+                                    "originating_tx_hash".to_string(),
+                                    serde_json::Value::Null,
+                                );
+                                extra_fields.insert("cross_block_receipt".to_string(), true.into());
+                            }
+                        }
+                    }
+                }
+                if opts.sharding_aware {
+                    extra_fields.insert(
+                        "shard_label".to_string(),
+                        format!("shard_{}", shard.shard_id).into(),
+                    );
+                    extra_fields.insert(
+                        "num_shards_in_block".to_string(),
+                        streamer_message.shards.len().into(),
+                    );
+                }
+                if opts.include_protocol_version {
+                    extra_fields.insert(
+                        "protocol_version".to_string(),
+                        streamer_message.block.header.latest_protocol_version.into(),
+                    );
+                }
+                if let Some(explorer_url) = opts.explorer_base_url().and_then(|explorer_base_url| {
+                    explorer_url(
+                        explorer_base_url,
+                        &state_change.cause,
+                        &streamer_message.block.header.hash,
+                    )
+                }) {
+                    extra_fields.insert("explorer_url".to_string(), explorer_url.into());
+                }
+                if opts.print_cause {
+                    if let Some(cause_context) = block_index.cause_context(&state_change.cause) {
+                        extra_fields.insert(
+                            "cause_context".to_string(),
+                            serde_json::to_value(cause_context)
+                                .expect("Failed to serialize CauseContext to JSON"),
+                        );
+                    }
+                }
+                if let Some(presigner) = &watcher_config.presigner {
+                    if raw_block_url.is_none() {
+                        raw_block_url = presigned_url(
+                            presigner
+                                .block_url(streamer_message.block.header.height)
+                                .await,
+                        );
+                    }
+                    if raw_shard_url.is_none() {
+                        raw_shard_url = presigned_url(
+                            presigner
+                                .shard_url(streamer_message.block.header.height, shard.shard_id)
+                                .await,
+                        );
+                    }
+                    if let Some(raw_block_url) = &raw_block_url {
+                        extra_fields
+                            .insert("raw_block_url".to_string(), raw_block_url.clone().into());
+                    }
+                    if let Some(raw_shard_url) = &raw_shard_url {
+                        extra_fields
+                            .insert("raw_shard_url".to_string(), raw_shard_url.clone().into());
+                    }
+                }
+                add_extra_fields(&mut changes_json, &extra_fields);
+                // The summary takes the changes of every output shape
+                if opts.summarize_by_account {
+                    projection::project_json(&mut changes_json, &include_fields, &exclude_fields);
+                    account_summaries
+                        .entry(state_change_account_id(state_change).clone())
+                        .or_default()
+                        .push(changes_json);
+                    continue;
+                }
+                if opts.near_wallet_integration && is_typed {
+                    let wallet_transaction = wallet::WalletTransaction::from_state_change(
+                        state_change,
+                        &block_index,
+                        streamer_message.block.header.hash,
+                        streamer_message.block.header.timestamp_nanosec,
+                    );
+                    // Wallet UIs expect valid JSON, so we print it on a single line
+                    println!(
+                        "{}",
+                        serde_json::to_string(&wallet_transaction)
+                            .expect("Failed to serialize WalletTransaction to JSON")
+                    );
+                    continue;
+                }
+                if opts.delta_encoding && is_typed {
+                    if let StateChangeValueView::AccountUpdate {
+                        account_id,
+                        account,
+                    } = &state_change.value
+                    {
+                        let previous = state
+                            .last_account_views
+                            .insert(account_id.clone(), account.clone());
+                        // We can print the delta only if we have seen this account before,
+                        // otherwise we fall through and print the full change
+                        if let Some(previous) = previous {
+                            let delta = delta::AccountDelta::compute(&previous, account);
+                            let mut delta_json = serde_json::to_value(delta)
+                                .expect("Failed to serialize AccountDelta to JSON");
+                            delta_json["block_height"] =
+                                streamer_message.block.header.height.into();
+                            delta_json["account_id"] = account_id.to_string().into();
+                            add_extra_fields(&mut delta_json, &extra_fields);
+                            projection::project_json(
+                                &mut delta_json,
+                                &include_fields,
+                                &exclude_fields,
+                            );
+                            if matches!(opts.event_ordering, EventOrdering::Natural) {
+                                print_event(
+                                    streamer_message.block.header.height,
+                                    "account_update_delta",
+                                    &delta_json,
+                                );
+                            } else {
+                                block_events.push(
+                                    account_id,
+                                    shard.shard_id,
+                                    event_ordering::type_priority(
+                                        &opts.event_type_priority,
+                                        "account_update",
+                                    ),
+                                    "account_update_delta".into(),
+                                    delta_json,
+                                );
+                            }
+                            continue;
+                        }
+                    }
+                }
+                let _serialization_timer = dev_timing.start(dev_timing::Step::Serialization);
+                // The type goes to the header even if the projection drops it
+                let change_type = changes_json["type"].clone();
+                projection::project_json(&mut changes_json, &include_fields, &exclude_fields);
+                if matches!(opts.event_ordering, EventOrdering::Natural) {
+                    println!("#{}. {}", streamer_message.block.header.height, change_type);
+                    println!("{:#?}", changes_json);
+                } else {
+                    block_events.push(
+                        state_change_account_id(state_change),
+                        shard.shard_id,
+                        event_ordering::type_priority(
+                            &opts.event_type_priority,
+                            change_type.as_str().unwrap_or_default(),
+                        ),
+                        change_type,
+                        changes_json,
+                    );
+                }
+            }
+        }
+
+        block_shard_stats.push(shard_stats::ShardStats {
+            shard_id: shard.shard_id,
+            processing_time_us: shard_started_at.elapsed().as_micros(),
+            total_state_changes: shard.state_changes.len() as u64,
+            matched_changes,
+            receipt_count: shard.receipt_execution_outcomes.len() as u64,
+        });
+    }
+
+    block_events.print_sorted(streamer_message.block.header.height, &opts.event_ordering);
+    if let Some(ecosystem_stats) = ecosystem_stats {
+        print_event(
+            streamer_message.block.header.height,
+            "ecosystem_stats",
+            &ecosystem_stats.finish(),
+        );
+    }
+    for (account_id, changes) in account_summaries {
+        let mut summary_json = serde_json::json!({
+            "account_id": account_id,
+            "block_height": streamer_message.block.header.height,
+            "changes": changes,
+        });
+        if opts.include_protocol_version {
+            summary_json["protocol_version"] =
+                streamer_message.block.header.latest_protocol_version.into();
+        }
+        print_event(
+            streamer_message.block.header.height,
+            "account_summary",
+            &summary_json,
+        );
+    }
+
+    state.blocks_handled += 1;
+    if opts.shard_stats {
+        print_event(
+            streamer_message.block.header.height,
+            "shard_stats",
+            &serde_json::json!({
+                "block_height": streamer_message.block.header.height,
+                "shards": block_shard_stats,
+            }),
+        );
+        if let Some(summary_every) = opts.shard_stats_summary_interval_blocks {
+            state
+                .shard_stats_summary
+                .add(streamer_message.block.header.height, &block_shard_stats);
+            if summary_every > 0 && state.shard_stats_summary.blocks >= summary_every {
+                let summary = state
+                    .shard_stats_summary
+                    .take(streamer_message.block.header.height);
+                print_event(
+                    streamer_message.block.header.height,
+                    "shard_stats_summary",
+                    &summary,
+                );
+            }
+        }
+    }
+    if let Some(report_every) = opts.accounts_report_every_n_blocks {
+        if report_every > 0 && state.blocks_handled % report_every == 0 {
+            print_accounts_report(streamer_message.block.header.height, opts, watcher_config);
+        }
+    }
+    if let Some(pagerduty) = &mut state.pagerduty {
+        pagerduty.resolve_stale(streamer_message.block.header.height);
+    }
+    if let Some(receipt_delay_tracker) = state.receipt_delay_tracker.as_mut() {
+        receipt_delay_tracker.forget_stale(streamer_message.block.header.height);
+    }
+    if opts.namespace_stats
+        && opts.namespace_stats_interval_blocks > 0
+        && state.blocks_handled % opts.namespace_stats_interval_blocks == 0
+    {
+        print_event(
+            streamer_message.block.header.height,
+            "namespace_stats",
+            &serde_json::json!({
+                "block_height": streamer_message.block.header.height,
+                "namespaces": state.namespace_stats.take(),
+            }),
+        );
+    }
+    if let Some(report_every) = opts.change_frequency_report {
+        if report_every > 0 && state.blocks_handled % report_every == 0 {
+            print_change_frequency_report(streamer_message.block.header.height, state);
+            if !opts.cumulative_frequency {
+                state.change_frequency.reset();
+            }
+        }
+    }
+
+    if let Some(record) = state.dev_timing.take(streamer_message.block.header.height) {
+        print_event(streamer_message.block.header.height, "dev_timing", &record);
+    }
+    Ok(())
+}
+
+/// Prints the watched accounts sorted by the number of changes to stderr
+/// to keep it apart from the changes themselves
+fn print_change_frequency_report(block_height: u64, state: &WatcherState) {
+    let accounts: Vec<serde_json::Value> = state
+        .change_frequency
+        .top_n(usize::MAX)
+        .into_iter()
+        .map(|(account_id, changes)| {
+            serde_json::json!({
+                "account_id": account_id,
+                "changes": changes,
+            })
+        })
+        .collect();
+    eprintln!(
+        "#{}. \"change_frequency_report\"\n{}",
+        block_height,
+        serde_json::json!({
+            "block_height": block_height,
+            "accounts": accounts,
+        })
+    );
+}
+
+/// Prints the watched accounts to stderr or writes them to the `--accounts-report-file`.
+/// The accounts are always exact names, the patterns can only exclude them
+fn print_accounts_report(block_height: u64, opts: &Opts, watcher_config: &WatcherConfig) {
+    let report = serde_json::json!({
+        "block_height": block_height,
+        "total_accounts": opts.accounts.len(),
+        "accounts": opts.accounts,
+        "denylist_patterns": watcher_config.denylist.patterns(),
+    });
+    match &opts.accounts_report_file {
+        Some(accounts_report_file) => {
+            if let Err(err) = std::fs::write(accounts_report_file, report.to_string()) {
+                eprintln!(
+                    "Failed to write {}: {}",
+                    accounts_report_file.display(),
+                    err
+                );
+            }
+        }
+        None => eprintln!("#{}. \"accounts_report\"\n{}", block_height, report),
+    }
+}
+
+/// Measures the delays of the receipts executed by the watched accounts.
+/// A receipt may be executed in the block it's created in, even in a shard
+/// before the one that has created it, so the receipts of all the shards
+/// are recorded before any of them is measured
+fn track_receipt_delays(
+    streamer_message: &near_lake_framework::near_indexer_primitives::StreamerMessage,
+    opts: &Opts,
+    watcher_config: &WatcherConfig,
+    state: &mut WatcherState,
+) {
+    let receipt_delay_tracker = match state.receipt_delay_tracker.as_mut() {
+        Some(receipt_delay_tracker) => receipt_delay_tracker,
+        None => return,
+    };
+    let block_height = streamer_message.block.header.height;
+    for shard in &streamer_message.shards {
+        if let Some(chunk) = &shard.chunk {
+            for transaction in &chunk.transactions {
+                receipt_delay_tracker.record_created(
+                    &transaction.outcome.execution_outcome.outcome.receipt_ids,
+                    block_height,
+                );
+            }
+        }
+        for outcome in &shard.receipt_execution_outcomes {
+            receipt_delay_tracker
+                .record_created(&outcome.execution_outcome.outcome.receipt_ids, block_height);
+        }
+    }
+    for outcome in streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+    {
+        if !is_account_watched(
+            &outcome.receipt.receiver_id,
+            &opts.accounts,
+            &state.extra_watching_list,
+            &watcher_config.denylist,
+            false,
+            watcher_config.hash_ring.as_ref(),
+        ) {
+            continue;
+        }
+        if let Some(alert) = receipt_delay_tracker.record_executed(
+            &outcome.receipt.receipt_id,
+            &outcome.receipt.predecessor_id,
+            &outcome.receipt.receiver_id,
+            block_height,
+        ) {
+            print_event(block_height, "receipt_delay_alert", &alert);
+        }
+    }
+}
+
+/// Checks the block is the next one after the last block we have received.
+/// NEAR may skip heights, so we compare the `prev_height` of the block
+/// with the last height rather than expect `last + 1`.
+/// Returns an error with `--verify-sequence-fatal`, so the watcher stops
+/// even in `--safe-mode`
+fn verify_sequence(
+    header: &near_lake_framework::near_indexer_primitives::views::BlockHeaderView,
+    opts: &Opts,
+    state: &mut WatcherState,
+) -> anyhow::Result<()> {
+    if let Some(last_block_height) = state.last_block_height {
+        let error = if header.height <= last_block_height {
+            Some("not_increasing")
+        } else if header.prev_height != Some(last_block_height) {
+            Some("missed_blocks")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            let error_json = serde_json::json!({
+                "error": error,
+                "expected_height": last_block_height + 1,
+                "actual_height": header.height,
+                "actual_prev_height": header.prev_height,
+            });
+            eprintln!(
+                "ERROR: block #{} doesn't follow #{}: {}",
+                header.height, last_block_height, error
+            );
+            print_event(header.height, "sequence_error", &error_json);
+            if opts.verify_sequence_fatal {
+                anyhow::bail!(
+                    "Block #{} doesn't follow #{}: {}",
+                    header.height,
+                    last_block_height,
+                    error
                 );
-                println!("{:#?}", changes_json);
             }
         }
     }
+    state.last_block_height = Some(header.height);
+    Ok(())
+}
+
+/// Builds the explorer link for the StateChange: the transaction page
+/// if the change was caused by a transaction or the block page
+/// for the validator accounts updates
+fn explorer_url(
+    explorer_base_url: &str,
+    cause: &StateChangeCauseView,
+    block_hash: &CryptoHash,
+) -> Option<String> {
+    match cause {
+        StateChangeCauseView::TransactionProcessing { tx_hash } => {
+            Some(format!("{}/transactions/{}", explorer_base_url, tx_hash))
+        }
+        StateChangeCauseView::ValidatorAccountsUpdate => {
+            Some(format!("{}/blocks/{}", explorer_base_url, block_hash))
+        }
+        _ => None,
+    }
+}
+
+fn add_extra_fields(
+    json: &mut serde_json::Value,
+    extra_fields: &serde_json::Map<String, serde_json::Value>,
+) {
+    if let Some(object) = json.as_object_mut() {
+        object.extend(extra_fields.clone());
+    }
+}
+
+/// Fetches the validators of the epoch that has just started and prints how
+/// they differ from the previous epoch. The first epoch we see is only remembered
+async fn track_validator_set(
+    validator_rpc: &rpc::JsonRpcClient,
+    epoch_id: &CryptoHash,
+    block_height: u64,
+    opts: &Opts,
+    state: &mut WatcherState,
+) {
+    let validators = match validator_set::fetch_validators(validator_rpc, epoch_id).await {
+        Ok(validators) => validators,
+        Err(err) => {
+            eprintln!(
+                "Failed to fetch the validators of epoch {} at #{}: {}",
+                epoch_id, block_height, err
+            );
+            return;
+        }
+    };
+    if let Some(previous) = &state.validators {
+        for event in validator_set::ValidatorSetDiff::compute(previous, &validators) {
+            let mut event_json = serde_json::to_value(&event)
+                .expect("Failed to serialize ValidatorSetEvent to JSON");
+            event_json["epoch_id"] = epoch_id.to_string().into();
+            event_json["block_height"] = block_height.into();
+            print_event(block_height, event.event_type, &event_json);
+        }
+    }
+    if opts.watch_validators {
+        state.extra_watching_list = validators
+            .iter()
+            .map(|validator| validator.account_id.clone())
+            .collect();
+    }
+    state.validators = Some(validators);
+}
+
+/// Sends the alert to all the configured alert destinations, `alert_type` is either
+/// the type of the change from `--critical-change-types` or the detected event type.
+/// PagerDuty gets the silenced alerts too, so the incident isn't resolved meanwhile.
+/// Without the silencer the alert is always sent
+fn send_critical_alert(
+    block_height: u64,
+    account_id: &AccountId,
+    alert_type: &str,
+    alert: &serde_json::Value,
+    watcher_config: &WatcherConfig,
+    pagerduty: &mut Option<pagerduty::PagerDuty>,
+    alert_silencer: Option<&mut alert_silence::AlertSilencer>,
+) {
+    let is_silenced = alert_silencer.map_or(false, |alert_silencer| {
+        alert_silencer.is_silenced(account_id, alert_type)
+    });
+    if !is_silenced {
+        if let Some(alert_webhook) = &watcher_config.alert_webhook {
+            alert_webhook.send(alert);
+        }
+        if let Some(slack) = &watcher_config.slack {
+            slack.send(block_height, account_id, alert_type, alert, true);
+        }
+        if let Some(discord) = &watcher_config.discord {
+            discord.send(block_height, account_id, alert_type, alert, true);
+        }
+    }
+    if let Some(pagerduty) = pagerduty {
+        pagerduty.trigger(
+            format!("{}/{}", account_id, alert_type),
+            format!(
+                "{} of {} at block #{}",
+                alert_type, account_id, block_height
+            ),
+            alert,
+            block_height,
+        );
+    }
+}
+
+/// Prints the event we have detected the same way we print the StateChanges
+fn print_event(block_height: u64, event_type: &str, event: &impl serde::Serialize) {
+    let event_json = match serde_json::to_value(event) {
+        Ok(event_json) => event_json,
+        Err(err) => {
+            eprintln!(
+                "Failed to serialize {} of block #{} to JSON: {}",
+                event_type, block_height, err
+            );
+            return;
+        }
+    };
+    println!("#{}. \"{}\"", block_height, event_type);
+    println!("{:#?}", event_json);
+}
+
+fn is_change_watched(
+    state_change: &StateChangeWithCauseView,
+    watching_list: &[AccountId],
+    extra_watching_list: &std::collections::HashSet<AccountId>,
+    denylist: &regex::RegexSet,
+    watch_all: bool,
+    hash_ring: Option<&consistent_hash::HashRing>,
+) -> bool {
+    is_account_watched(
+        state_change_account_id(state_change),
+        watching_list,
+        extra_watching_list,
+        denylist,
+        watch_all,
+        hash_ring,
+    )
+}
+
+fn is_account_watched(
+    account_id: &AccountId,
+    watching_list: &[AccountId],
+    extra_watching_list: &std::collections::HashSet<AccountId>,
+    denylist: &regex::RegexSet,
+    watch_all: bool,
+    hash_ring: Option<&consistent_hash::HashRing>,
+) -> bool {
+    // check the watching_list has the affected account_id from the state_change
+    // and the account is not excluded by the denylist (denylist always wins)
+    (watch_all || watching_list.contains(account_id) || extra_watching_list.contains(account_id))
+        && hash_ring.map_or(true, |hash_ring| hash_ring.is_assigned(account_id.as_ref()))
+        && !denylist.is_match(account_id.as_ref())
+}
+
+/// The `type` the change has in the JSON output
+fn change_type(value: &StateChangeValueView) -> &'static str {
+    match value {
+        StateChangeValueView::AccountUpdate { .. } => "account_update",
+        StateChangeValueView::AccountDeletion { .. } => "account_deletion",
+        StateChangeValueView::AccessKeyUpdate { .. } => "access_key_update",
+        StateChangeValueView::AccessKeyDeletion { .. } => "access_key_deletion",
+        StateChangeValueView::DataUpdate { .. } => "data_update",
+        StateChangeValueView::DataDeletion { .. } => "data_deletion",
+        StateChangeValueView::ContractCodeUpdate { .. } => "contract_code_update",
+        StateChangeValueView::ContractCodeDeletion { .. } => "contract_code_deletion",
+    }
 }
 
-fn is_change_watched(state_change: &StateChangeWithCauseView, watching_list: &[AccountId]) -> bool {
+fn state_change_account_id(state_change: &StateChangeWithCauseView) -> &AccountId {
     // get the affected account_id from state_change.value
     // ref https://docs.rs/near-primitives/0.12.0/near_primitives/views/enum.StateChangeValueView.html
-    let account_id = match &state_change.value {
+    match &state_change.value {
         StateChangeValueView::AccountUpdate { account_id, .. } => account_id,
         StateChangeValueView::AccountDeletion { account_id } => account_id,
         StateChangeValueView::AccessKeyUpdate { account_id, .. } => account_id,
@@ -95,8 +2191,199 @@ fn is_change_watched(state_change: &StateChangeWithCauseView, watching_list: &[A
         StateChangeValueView::DataDeletion { account_id, .. } => account_id,
         StateChangeValueView::ContractCodeUpdate { account_id, .. } => account_id,
         StateChangeValueView::ContractCodeDeletion { account_id, .. } => account_id,
-    };
+    }
+}
 
-    // check the watching_list has the affected account_id from the state_change
-    watching_list.contains(account_id)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_deletion(account_id: &str) -> StateChangeWithCauseView {
+        serde_json::from_value(serde_json::json!({
+            "cause": {"type": "not_writable_to_disk"},
+            "type": "account_deletion",
+            "change": {"account_id": account_id},
+        }))
+        .unwrap()
+    }
+
+    fn opts(args: &[&str]) -> Opts {
+        let mut argv = vec!["watcher", "--block-height", "1"];
+        argv.extend_from_slice(args);
+        argv.push("mainnet");
+        Opts::try_parse_args_from(argv).unwrap()
+    }
+
+    fn denylist(args: &[&str]) -> regex::RegexSet {
+        opts(args).denylist().unwrap()
+    }
+
+    /// Headers of the blocks `(height, prev_height)` as the stream would deliver them
+    fn block_headers(
+        heights: &[(u64, u64)],
+    ) -> Vec<near_lake_framework::near_indexer_primitives::views::BlockHeaderView> {
+        let hash = CryptoHash::default().to_string();
+        heights
+            .iter()
+            .map(|(height, prev_height)| {
+                serde_json::from_value(serde_json::json!({
+                    "height": height,
+                    "prev_height": prev_height,
+                    "epoch_id": hash,
+                    "next_epoch_id": hash,
+                    "hash": hash,
+                    "prev_hash": hash,
+                    "prev_state_root": hash,
+                    "chunk_receipts_root": hash,
+                    "chunk_headers_root": hash,
+                    "chunk_tx_root": hash,
+                    "outcome_root": hash,
+                    "chunks_included": 4,
+                    "challenges_root": hash,
+                    "timestamp": 0,
+                    "timestamp_nanosec": "0",
+                    "random_value": hash,
+                    "validator_proposals": [],
+                    "chunk_mask": [],
+                    "gas_price": "100000000",
+                    "block_ordinal": null,
+                    "rent_paid": "0",
+                    "validator_reward": "0",
+                    "total_supply": "0",
+                    "challenges_result": [],
+                    "last_final_block": hash,
+                    "last_ds_final_block": hash,
+                    "next_bp_hash": hash,
+                    "block_merkle_root": hash,
+                    "epoch_sync_data_hash": null,
+                    "approvals": [],
+                    "signature": format!("ed25519:{}", "1".repeat(64)),
+                    "latest_protocol_version": 55,
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn is_watched(
+        account_id: &str,
+        watching_list: &[AccountId],
+        denylist: &regex::RegexSet,
+    ) -> bool {
+        is_change_watched(
+            &account_deletion(account_id),
+            watching_list,
+            &std::collections::HashSet::new(),
+            denylist,
+            false,
+            None,
+        )
+    }
+
+    #[test]
+    fn subcommands_dont_need_the_watcher_options() {
+        let opts = Opts::try_parse_args_from(["watcher", "generate-completions", "bash"]).unwrap();
+        assert!(matches!(
+            opts.command,
+            Command::GenerateCompletions {
+                shell: clap_complete::Shell::Bash
+            }
+        ));
+        let opts =
+            Opts::try_parse_args_from(["watcher", "debug-block", "9820210", "testnet"]).unwrap();
+        assert!(matches!(
+            opts.command,
+            Command::DebugBlock {
+                height: 9820210,
+                chain_id: ChainId::Testnet
+            }
+        ));
+        assert!(matches!(opts.chain_id(), ChainId::Testnet));
+        // The watcher still needs a height to start from
+        assert!(Opts::try_parse_args_from(["watcher", "mainnet"]).is_err());
+    }
+
+    #[test]
+    fn completions_include_every_subcommand() {
+        let mut script = vec![];
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut Opts::command(),
+            "near-lake-accounts-watcher",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+        for subcommand in ["mainnet", "custom", "generate-completions", "debug-block"] {
+            assert!(script.contains(subcommand), "{} is missing", subcommand);
+        }
+    }
+
+    #[test]
+    fn denylist_wins_over_watching_list() {
+        let watching_list: Vec<AccountId> =
+            vec!["alice.near".parse().unwrap(), "bob.near".parse().unwrap()];
+        let denylist = denylist(&["--denylist-account", "alice.near"]);
+        assert!(!is_watched("alice.near", &watching_list, &denylist));
+        assert!(is_watched("bob.near", &watching_list, &denylist));
+    }
+
+    #[test]
+    fn denylist_matches_exact_names_literally() {
+        let watching_list: Vec<AccountId> =
+            vec!["a.near".parse().unwrap(), "abnear".parse().unwrap()];
+        let denylist = denylist(&["--denylist-account", "a.near"]);
+        assert!(!is_watched("a.near", &watching_list, &denylist));
+        // The dot isn't a wildcard and the name isn't a prefix either
+        assert!(is_watched("abnear", &watching_list, &denylist));
+        assert!(!denylist.is_match("sub.a.near"));
+    }
+
+    #[test]
+    fn denylist_keeps_patterns_as_regexes() {
+        let watching_list: Vec<AccountId> =
+            vec!["x.sweat".parse().unwrap(), "alice.near".parse().unwrap()];
+        let denylist = denylist(&["--denylist-account", ".*\\.sweat"]);
+        assert!(!is_watched("x.sweat", &watching_list, &denylist));
+        assert!(is_watched("alice.near", &watching_list, &denylist));
+    }
+
+    #[test]
+    fn verify_sequence_reports_out_of_order_blocks() {
+        let opts = opts(&["--verify-sequence"]);
+        let mut state = WatcherState::default();
+        // #12 is skipped by the chain, #14 arrives late and #13 after it is a step back
+        let headers = block_headers(&[(10, 9), (11, 10), (13, 11), (15, 13), (14, 13), (13, 11)]);
+        for header in &headers {
+            verify_sequence(header, &opts, &mut state).unwrap();
+        }
+        assert_eq!(state.last_block_height, Some(13));
+    }
+
+    #[test]
+    fn verify_sequence_fatal_returns_error() {
+        let opts = opts(&["--verify-sequence", "--verify-sequence-fatal"]);
+        let mut state = WatcherState::default();
+        let headers = block_headers(&[(10, 9), (11, 10), (13, 11), (15, 14)]);
+        for header in &headers[..3] {
+            verify_sequence(header, &opts, &mut state).unwrap();
+        }
+        let err = verify_sequence(&headers[3], &opts, &mut state).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Block #15 doesn't follow #13: missed_blocks"
+        );
+    }
+
+    #[test]
+    fn verify_sequence_fatal_rejects_repeated_block() {
+        let opts = opts(&["--verify-sequence", "--verify-sequence-fatal"]);
+        let mut state = WatcherState::default();
+        let headers = block_headers(&[(10, 9), (10, 9)]);
+        verify_sequence(&headers[0], &opts, &mut state).unwrap();
+        let err = verify_sequence(&headers[1], &opts, &mut state).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Block #10 doesn't follow #10: not_increasing"
+        );
+    }
 }
@@ -0,0 +1,64 @@
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+
+/// How long we stop reading the stream at most, waiting for the memory to be released.
+/// The allocator doesn't always give the freed memory back to the OS,
+/// so we don't wait for the resident size to drop forever
+const MAX_PAUSE: std::time::Duration = std::time::Duration::from_secs(10);
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The watcher has crossed `--max-memory-mb` and stopped reading the stream
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct MemoryPressureEvent {
+    pub resident_mb: u64,
+    pub max_memory_mb: u64,
+    /// Resident size after the buffers are flushed and the pause is over
+    pub resident_mb_after_pause: Option<u64>,
+    pub paused_ms: u64,
+    pub block_height: BlockHeight,
+}
+
+/// Resident set size of the process, read from `/proc/self/status`
+/// (which is only there on Linux)
+pub(crate) fn resident_memory_mb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let vm_rss_kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(vm_rss_kb / 1024)
+}
+
+/// Returns the resident size if it is over the cap
+pub(crate) fn over_cap(max_memory_mb: u64) -> Option<u64> {
+    resident_memory_mb().filter(|resident_mb| *resident_mb > max_memory_mb)
+}
+
+/// Doesn't read the stream until the memory goes under the cap or `MAX_PAUSE` passes.
+/// The stream channel is bounded, so the Lake Framework stops fetching the blocks
+/// meanwhile, and the background webhook, Slack and Discord requests get time to finish
+pub(crate) async fn wait_for_release(
+    resident_mb: u64,
+    max_memory_mb: u64,
+    block_height: BlockHeight,
+) -> MemoryPressureEvent {
+    let started_at = tokio::time::Instant::now();
+    let mut resident_mb_after_pause = None;
+    while started_at.elapsed() < MAX_PAUSE {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        resident_mb_after_pause = resident_memory_mb();
+        if resident_mb_after_pause.map_or(true, |resident_mb| resident_mb <= max_memory_mb) {
+            break;
+        }
+    }
+    MemoryPressureEvent {
+        resident_mb,
+        max_memory_mb,
+        resident_mb_after_pause,
+        paused_ms: started_at.elapsed().as_millis() as u64,
+        block_height,
+    }
+}
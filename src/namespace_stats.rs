@@ -0,0 +1,66 @@
+use std::collections::{BTreeMap, HashSet};
+
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+
+/// Totals of a namespace since the last report
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct NamespaceStats {
+    pub namespace: String,
+    pub unique_accounts: usize,
+    pub total_changes: u64,
+    pub change_type_breakdown: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Debug, Default)]
+struct NamespaceCounts {
+    accounts: HashSet<AccountId>,
+    total_changes: u64,
+    change_types: BTreeMap<&'static str, u64>,
+}
+
+/// Groups the matched changes by the account namespace
+#[derive(Debug, Default)]
+pub(crate) struct NamespaceStatsTracker {
+    namespaces: BTreeMap<String, NamespaceCounts>,
+}
+
+impl NamespaceStatsTracker {
+    pub(crate) fn record(&mut self, account_id: &AccountId, change_type: &'static str) {
+        let counts = self
+            .namespaces
+            .entry(namespace(account_id.as_ref()).to_string())
+            .or_default();
+        if !counts.accounts.contains(account_id) {
+            counts.accounts.insert(account_id.clone());
+        }
+        counts.total_changes += 1;
+        *counts.change_types.entry(change_type).or_insert(0) += 1;
+    }
+
+    /// Returns the stats of every namespace, the most active first, and starts over
+    pub(crate) fn take(&mut self) -> Vec<NamespaceStats> {
+        let mut stats: Vec<NamespaceStats> = std::mem::take(&mut self.namespaces)
+            .into_iter()
+            .map(|(namespace, counts)| NamespaceStats {
+                namespace,
+                unique_accounts: counts.accounts.len(),
+                total_changes: counts.total_changes,
+                change_type_breakdown: counts.change_types,
+            })
+            .collect();
+        // Stable sort keeps the alphabetical order of the BTreeMap for the same totals
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.total_changes));
+        stats
+    }
+}
+
+/// The account right under the top-level account: `myapp.near` for both
+/// `alice.myapp.near` and `myapp.near`. The top-level accounts themselves,
+/// like `aurora` or the implicit accounts, are namespaces of their own
+pub(crate) fn namespace(account_id: &str) -> &str {
+    let mut dots = account_id.rmatch_indices('.').map(|(index, _)| index);
+    match (dots.next(), dots.next()) {
+        (Some(_), Some(second_last_dot)) => &account_id[second_last_dot + 1..],
+        _ => account_id,
+    }
+}
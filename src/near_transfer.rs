@@ -0,0 +1,72 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ExecutionStatusView, ReceiptEnumView,
+};
+use near_lake_framework::near_indexer_primitives::IndexerExecutionOutcomeWithReceipt;
+
+/// Gas refunds come from the system account, they aren't transfers between accounts
+const SYSTEM_ACCOUNT: &str = "system";
+
+/// NEAR attached to a receipt to a watched account
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct NearTransferEvent {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount_yocto: String,
+    /// `transfer` for a plain `Transfer` action or `function_call` for the deposit
+    /// attached to a call
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method_name: Option<String>,
+    pub receipt_id: String,
+    pub block_height: BlockHeight,
+}
+
+/// Native NEAR sends don't produce a log, so we read the deposits
+/// of the receipt that has caused the `AccountUpdate`
+pub(crate) fn near_transfer_events(
+    outcome: &IndexerExecutionOutcomeWithReceipt,
+    block_height: BlockHeight,
+) -> Vec<NearTransferEvent> {
+    let receipt = &outcome.receipt;
+    if receipt.predecessor_id.as_ref() == SYSTEM_ACCOUNT {
+        return vec![];
+    }
+    // The deposit of a failed receipt is refunded to the sender
+    if !matches!(
+        outcome.execution_outcome.outcome.status,
+        ExecutionStatusView::SuccessValue(_) | ExecutionStatusView::SuccessReceiptId(_)
+    ) {
+        return vec![];
+    }
+    let actions = match &receipt.receipt {
+        ReceiptEnumView::Action { actions, .. } => actions,
+        ReceiptEnumView::Data { .. } => return vec![],
+    };
+    actions
+        .iter()
+        .filter_map(|action| {
+            let (kind, method_name, deposit) = match action {
+                ActionView::Transfer { deposit } => ("transfer", None, *deposit),
+                ActionView::FunctionCall {
+                    method_name,
+                    deposit,
+                    ..
+                } => ("function_call", Some(method_name.clone()), *deposit),
+                _ => return None,
+            };
+            if deposit == 0 {
+                return None;
+            }
+            Some(NearTransferEvent {
+                sender_id: receipt.predecessor_id.clone(),
+                receiver_id: receipt.receiver_id.clone(),
+                amount_yocto: deposit.to_string(),
+                kind,
+                method_name,
+                receipt_id: receipt.receipt_id.to_string(),
+                block_height,
+            })
+        })
+        .collect()
+}
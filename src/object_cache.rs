@@ -0,0 +1,245 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+use near_lake_framework::near_indexer_primitives::StreamerMessage;
+
+/// Keeps the received blocks as `<dir>/<block_height>.json.gz`, so a restarted
+/// watcher replays them from the disk instead of fetching them from S3 again
+/// (used by `--s3-object-cache`)
+pub(crate) struct ObjectCache {
+    dir: PathBuf,
+    max_size_bytes: Option<u64>,
+    /// Cached files with their sizes, the oldest first. A file stored again
+    /// leaves its old entry behind, which is skipped as it isn't in `index`
+    files: VecDeque<(PathBuf, u64)>,
+    /// The sequence number of the entry of each file in `files`
+    index: HashMap<PathBuf, u64>,
+    /// The sequence number of the first entry in `files`
+    front_sequence: u64,
+    size_bytes: u64,
+}
+
+impl ObjectCache {
+    pub(crate) fn open(dir: PathBuf, max_size_mb: Option<u64>) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| anyhow::anyhow!("Failed to create {}: {}", dir.display(), err))?;
+        let mut files = vec![];
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                files.push((entry.path(), metadata.len(), metadata.modified()?));
+            }
+        }
+        files.sort_by_key(|(_, _, modified)| *modified);
+        let size_bytes = files.iter().map(|(_, size, _)| size).sum();
+        Ok(Self {
+            dir,
+            max_size_bytes: max_size_mb.map(|max_size_mb| max_size_mb * 1024 * 1024),
+            index: files
+                .iter()
+                .zip(0..)
+                .map(|((path, _, _), sequence)| (path.clone(), sequence))
+                .collect(),
+            files: files
+                .into_iter()
+                .map(|(path, size, _)| (path, size))
+                .collect(),
+            front_sequence: 0,
+            size_bytes,
+        })
+    }
+
+    /// The entries of the cached files, the oldest first
+    fn cached_files(&self) -> impl Iterator<Item = &(PathBuf, u64)> {
+        self.files
+            .iter()
+            .zip(self.front_sequence..)
+            .filter(|((path, _), sequence)| self.index.get(path) == Some(sequence))
+            .map(|(file, _)| file)
+    }
+
+    /// Block heights in the cache from the given one, the lowest first
+    pub(crate) fn heights_from(&self, block_height: BlockHeight) -> Vec<BlockHeight> {
+        let mut heights: Vec<BlockHeight> = self
+            .cached_files()
+            .filter_map(|(path, _)| {
+                path.file_name()?
+                    .to_str()?
+                    .strip_suffix(".json.gz")?
+                    .parse()
+                    .ok()
+            })
+            .filter(|height| *height >= block_height)
+            .collect();
+        heights.sort_unstable();
+        heights
+    }
+
+    pub(crate) fn load(&self, block_height: BlockHeight) -> anyhow::Result<StreamerMessage> {
+        let path = self.path(block_height);
+        let file = std::fs::File::open(&path)
+            .map_err(|err| anyhow::anyhow!("Failed to open {}: {}", path.display(), err))?;
+        let mut content = vec![];
+        flate2::read::GzDecoder::new(file).read_to_end(&mut content)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    pub(crate) fn store(&mut self, streamer_message: &StreamerMessage) -> anyhow::Result<()> {
+        let path = self.path(streamer_message.block.header.height);
+        let file = std::fs::File::create(&path)
+            .map_err(|err| anyhow::anyhow!("Failed to create {}: {}", path.display(), err))?;
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        encoder.write_all(&serde_json::to_vec(streamer_message)?)?;
+        let size = encoder.finish()?.metadata()?.len();
+        self.add_file(path, size);
+        Ok(())
+    }
+
+    /// A block stored again (e.g. after a restart) replaces its old file,
+    /// so it is counted once and moves to the newest end of the queue
+    fn add_file(&mut self, path: PathBuf, size: u64) {
+        if let Some(sequence) = self.index.remove(&path) {
+            let (_, old_size) = self.files[(sequence - self.front_sequence) as usize];
+            self.size_bytes -= old_size;
+        }
+        self.index
+            .insert(path.clone(), self.front_sequence + self.files.len() as u64);
+        self.files.push_back((path, size));
+        self.size_bytes += size;
+        self.evict();
+    }
+
+    /// Removes the oldest files until the cache fits into `--s3-cache-max-size-mb`
+    fn evict(&mut self) {
+        let max_size_bytes = match self.max_size_bytes {
+            Some(max_size_bytes) => max_size_bytes,
+            None => return,
+        };
+        while self.size_bytes > max_size_bytes {
+            let (path, size) = match self.files.pop_front() {
+                Some(file) => file,
+                None => break,
+            };
+            let sequence = self.front_sequence;
+            self.front_sequence += 1;
+            if self.index.get(&path) != Some(&sequence) {
+                continue;
+            }
+            self.index.remove(&path);
+            if let Err(err) = std::fs::remove_file(&path) {
+                eprintln!("Failed to evict {}: {}", path.display(), err);
+            }
+            self.size_bytes -= size;
+        }
+    }
+
+    fn path(&self, block_height: BlockHeight) -> PathBuf {
+        self.dir.join(format!("{}.json.gz", block_height))
+    }
+}
+
+/// Replays the cached blocks from the start height while they follow each other,
+/// then starts the NEAR Lake streamer after the last of them and caches
+/// the blocks it receives
+pub(crate) fn cached_stream(
+    mut cache: ObjectCache,
+    mut config: near_lake_framework::LakeConfig,
+) -> tokio::sync::mpsc::Receiver<StreamerMessage> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    tokio::spawn(async move {
+        let mut last_height = None;
+        for height in cache.heights_from(config.start_block_height) {
+            let streamer_message = match cache.load(height) {
+                Ok(streamer_message) => streamer_message,
+                Err(err) => {
+                    eprintln!("Failed to read the cached block #{}: {}", height, err);
+                    break;
+                }
+            };
+            // A gap in the cache has to be fetched from S3
+            let prev_height = streamer_message.block.header.prev_height;
+            let is_next = match last_height {
+                Some(last_height) => prev_height == Some(last_height),
+                None => {
+                    prev_height.map_or(true, |prev_height| prev_height < config.start_block_height)
+                }
+            };
+            if !is_next {
+                break;
+            }
+            last_height = Some(height);
+            if sender.send(streamer_message).await.is_err() {
+                return;
+            }
+        }
+        if let Some(last_height) = last_height {
+            eprintln!("Replayed the cached blocks up to #{}", last_height);
+            config.start_block_height = last_height + 1;
+        }
+
+        let (_, mut stream) = near_lake_framework::streamer(config);
+        while let Some(streamer_message) = stream.recv().await {
+            if let Err(err) = cache.store(&streamer_message) {
+                eprintln!(
+                    "Failed to cache block #{}: {}",
+                    streamer_message.block.header.height, err
+                );
+            }
+            if sender.send(streamer_message).await.is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_size_mb: Option<u64>) -> ObjectCache {
+        ObjectCache {
+            // Nothing is written there, the evicted files just fail to be removed
+            dir: std::env::temp_dir().join("near-lake-accounts-watcher-object-cache-test"),
+            max_size_bytes: max_size_mb.map(|max_size_mb| max_size_mb * 1024 * 1024),
+            files: VecDeque::new(),
+            index: HashMap::new(),
+            front_sequence: 0,
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn storing_cached_height_replaces_its_entry() {
+        let mut cache = cache(None);
+        cache.add_file(cache.path(1), 100);
+        cache.add_file(cache.path(2), 200);
+        cache.add_file(cache.path(1), 150);
+        assert_eq!(cache.size_bytes, 350);
+        assert_eq!(
+            cache.cached_files().cloned().collect::<Vec<_>>(),
+            [(cache.path(2), 200), (cache.path(1), 150)]
+        );
+        assert_eq!(cache.heights_from(0), [1, 2]);
+    }
+
+    #[test]
+    fn evicts_oldest_files_over_the_cap() {
+        let mut cache = cache(Some(1));
+        cache.add_file(cache.path(1), 600 * 1024);
+        cache.add_file(cache.path(2), 300 * 1024);
+        // Storing #1 again makes #2 the oldest
+        cache.add_file(cache.path(1), 700 * 1024);
+        cache.add_file(cache.path(3), 200 * 1024);
+        assert_eq!(
+            cache.cached_files().cloned().collect::<Vec<_>>(),
+            [(cache.path(1), 700 * 1024), (cache.path(3), 200 * 1024)]
+        );
+        // The stale entry of #1 has been dropped along with #2
+        assert_eq!(cache.files.len(), 2);
+        assert_eq!(cache.size_bytes, 900 * 1024);
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// Each price feed is stored under the prefix followed by the asset id (UTF-8)
+const PRICE_KEY_PREFIX: &[u8] = b"PRICE";
+
+/// The price reported by the oracle
+#[derive(Debug, BorshDeserialize)]
+struct Price {
+    multiplier: u128,
+    decimals: u8,
+    block_timestamp: u64,
+}
+
+/// The price feed decoded from the oracle's storage
+#[derive(Debug)]
+pub(crate) struct PriceUpdate {
+    pub asset_id: String,
+    /// `multiplier / 10^decimals`, good enough for display and the deviation check
+    pub price_usd: f64,
+    pub block_timestamp: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct OraclePriceEvent {
+    pub oracle_id: AccountId,
+    pub asset_id: String,
+    pub price_usd: f64,
+    pub block_height: BlockHeight,
+    pub block_timestamp: u64,
+}
+
+/// The price has moved more than `--oracle-price-deviation-alert` since the previous update
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct OracleDeviationAlert {
+    pub oracle_id: AccountId,
+    pub asset_id: String,
+    pub previous_price_usd: f64,
+    pub price_usd: f64,
+    pub deviation_pct: f64,
+    pub block_height: BlockHeight,
+}
+
+pub(crate) struct OracleDecoder;
+
+impl OracleDecoder {
+    pub(crate) fn decode_price_key_value(key: &[u8], value: &[u8]) -> Option<PriceUpdate> {
+        let asset_id = std::str::from_utf8(key.strip_prefix(PRICE_KEY_PREFIX)?).ok()?;
+        let price = Price::try_from_slice(value).ok()?;
+        Some(PriceUpdate {
+            asset_id: asset_id.to_string(),
+            price_usd: price.multiplier as f64 / 10f64.powi(i32::from(price.decimals)),
+            block_timestamp: price.block_timestamp,
+        })
+    }
+}
+
+/// Remembers the last price of each feed to compare it with the next update
+#[derive(Debug, Default)]
+pub(crate) struct OraclePriceTracker {
+    last_prices: HashMap<(AccountId, String), f64>,
+}
+
+impl OraclePriceTracker {
+    /// Returns the alert if the price has changed by more than `max_deviation_pct`
+    /// since the previous update of the same feed
+    pub(crate) fn check(
+        &mut self,
+        oracle_id: &AccountId,
+        price_update: &PriceUpdate,
+        max_deviation_pct: f64,
+        block_height: BlockHeight,
+    ) -> Option<OracleDeviationAlert> {
+        let previous_price_usd = self.last_prices.insert(
+            (oracle_id.clone(), price_update.asset_id.clone()),
+            price_update.price_usd,
+        )?;
+        if previous_price_usd == 0.0 {
+            return None;
+        }
+        let deviation_pct =
+            (price_update.price_usd - previous_price_usd).abs() / previous_price_usd * 100.0;
+        if deviation_pct <= max_deviation_pct {
+            return None;
+        }
+        Some(OracleDeviationAlert {
+            oracle_id: oracle_id.clone(),
+            asset_id: price_update.asset_id.clone(),
+            previous_price_usd,
+            price_usd: price_update.price_usd,
+            deviation_pct,
+            block_height,
+        })
+    }
+}
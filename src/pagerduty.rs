@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+
+use crate::webhook::post_in_background;
+
+const EVENTS_API_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Triggers PagerDuty incidents for the critical alerts (used by `--alert-pagerduty`).
+/// The alerts of the same account and change type share the incident, and it is
+/// resolved once they stop coming for `--pagerduty-resolve-after-blocks`
+pub(crate) struct PagerDuty {
+    client: reqwest::Client,
+    routing_key: String,
+    resolve_after_blocks: u64,
+    /// The last block each open incident was triggered at by its dedup key
+    triggered: HashMap<String, BlockHeight>,
+}
+
+impl PagerDuty {
+    pub(crate) fn new(routing_key: String, resolve_after_blocks: u64) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            routing_key,
+            resolve_after_blocks,
+            triggered: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn trigger(
+        &mut self,
+        dedup_key: String,
+        summary: String,
+        custom_details: &serde_json::Value,
+        block_height: BlockHeight,
+    ) {
+        self.send(serde_json::json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": dedup_key,
+            "payload": {
+                "summary": summary,
+                "source": "near-lake-accounts-watcher",
+                "severity": "critical",
+                "custom_details": custom_details,
+            },
+        }));
+        self.triggered.insert(dedup_key, block_height);
+    }
+
+    /// Resolves the incidents that haven't been triggered again for a while
+    pub(crate) fn resolve_stale(&mut self, block_height: BlockHeight) {
+        let resolve_after_blocks = self.resolve_after_blocks;
+        let stale: Vec<String> = self
+            .triggered
+            .iter()
+            .filter(|(_, triggered_at)| {
+                block_height.saturating_sub(**triggered_at) >= resolve_after_blocks
+            })
+            .map(|(dedup_key, _)| dedup_key.clone())
+            .collect();
+        for dedup_key in stale {
+            self.triggered.remove(&dedup_key);
+            self.send(serde_json::json!({
+                "routing_key": self.routing_key,
+                "event_action": "resolve",
+                "dedup_key": dedup_key,
+            }));
+        }
+    }
+
+    fn send(&self, event: serde_json::Value) {
+        post_in_background(self.client.post(EVENTS_API_URL).json(&event), "PagerDuty");
+    }
+}
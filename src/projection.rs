@@ -0,0 +1,158 @@
+use serde_json::Value;
+
+/// Keeps only the `include` paths (all the fields if there are none) and then
+/// removes the `exclude` paths, the paths are dot-separated field names like
+/// `change.code_hash`. A path goes through the arrays into each of their items
+pub(crate) fn project_json(value: &mut Value, include: &[&str], exclude: &[&str]) {
+    if !include.is_empty() {
+        let include: Vec<Vec<&str>> = include
+            .iter()
+            .map(|path| path.split('.').collect())
+            .collect();
+        let include: Vec<&[&str]> = include.iter().map(Vec::as_slice).collect();
+        *value = keep(value.take(), &include);
+    }
+    for path in exclude {
+        remove(value, &path.split('.').collect::<Vec<_>>());
+    }
+}
+
+fn keep(value: Value, paths: &[&[&str]]) -> Value {
+    // The whole node is included by one of the paths
+    if paths.iter().any(|path| path.is_empty()) {
+        return value;
+    }
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter_map(|(name, field)| {
+                    let field_paths: Vec<&[&str]> = paths
+                        .iter()
+                        .filter(|path| path[0] == name)
+                        .map(|path| &path[1..])
+                        .collect();
+                    if field_paths.is_empty() {
+                        return None;
+                    }
+                    let field = keep(field, &field_paths);
+                    Some((name, field))
+                })
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|item| keep(item, paths)).collect())
+        }
+        // The path goes deeper than the value, there is nothing to cut
+        value => value,
+    }
+}
+
+fn remove(value: &mut Value, path: &[&str]) {
+    match (value, path) {
+        (_, []) => {}
+        (Value::Object(fields), [name]) => {
+            fields.remove(*name);
+        }
+        (Value::Object(fields), [name, rest @ ..]) => {
+            if let Some(field) = fields.get_mut(*name) {
+                remove(field, rest);
+            }
+        }
+        (Value::Array(items), path) => {
+            for item in items {
+                remove(item, path);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn change() -> Value {
+        json!({
+            "type": "account_update",
+            "cause": {"type": "receipt_processing", "receipt_hash": "abc"},
+            "change": {
+                "account_id": "alice.near",
+                "amount": "100",
+                "code_hash": "11111111111111111111111111111111",
+            },
+        })
+    }
+
+    #[test]
+    fn includes_nested_object_path() {
+        let mut value = change();
+        project_json(&mut value, &["change.amount", "type"], &[]);
+        assert_eq!(
+            value,
+            json!({"type": "account_update", "change": {"amount": "100"}})
+        );
+    }
+
+    #[test]
+    fn excludes_nested_object_path() {
+        let mut value = change();
+        project_json(&mut value, &[], &["change.code_hash", "cause.receipt_hash"]);
+        assert_eq!(
+            value,
+            json!({
+                "type": "account_update",
+                "cause": {"type": "receipt_processing"},
+                "change": {"account_id": "alice.near", "amount": "100"},
+            })
+        );
+    }
+
+    #[test]
+    fn goes_through_arrays() {
+        let mut value = json!({
+            "keys": [
+                {"public_key": "ed25519:a", "nonce": 1},
+                {"public_key": "ed25519:b", "nonce": 2},
+            ],
+            "other": true,
+        });
+        project_json(&mut value, &["keys.public_key"], &[]);
+        assert_eq!(
+            value,
+            json!({"keys": [{"public_key": "ed25519:a"}, {"public_key": "ed25519:b"}]})
+        );
+
+        let mut value = json!({"keys": [{"public_key": "ed25519:a", "nonce": 1}]});
+        project_json(&mut value, &[], &["keys.nonce"]);
+        assert_eq!(value, json!({"keys": [{"public_key": "ed25519:a"}]}));
+    }
+
+    #[test]
+    fn exclude_wins_over_include_of_the_same_subtree() {
+        let mut value = change();
+        project_json(&mut value, &["change"], &["change.code_hash"]);
+        assert_eq!(
+            value,
+            json!({"change": {"account_id": "alice.near", "amount": "100"}})
+        );
+    }
+
+    #[test]
+    fn ignores_missing_paths() {
+        let mut value = change();
+        project_json(
+            &mut value,
+            &[],
+            &["change.missing", "missing.deeper", "type.deeper"],
+        );
+        assert_eq!(value, change());
+
+        let mut value = change();
+        project_json(&mut value, &["missing", "type.deeper"], &[]);
+        // The path below a string keeps the whole string
+        assert_eq!(value, json!({"type": "account_update"}));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashMap};
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+/// Receipts we haven't seen executed for that many blocks are forgotten,
+/// most of them are sent to the accounts we don't watch
+const FORGET_AFTER_BLOCKS: BlockHeight = 1000;
+
+/// The receipt was executed later than `--max-receipt-delay-blocks`
+/// after the block it had been created in
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ReceiptDelayAlert {
+    pub receipt_id: CryptoHash,
+    pub predecessor_id: AccountId,
+    pub receiver_id: AccountId,
+    pub created_at_block_height: BlockHeight,
+    pub executed_at_block_height: BlockHeight,
+    pub delay_blocks: u64,
+    pub max_delay_blocks: u64,
+    /// Number of the executed watched receipts per delay in blocks
+    pub delay_histogram: BTreeMap<u64, u64>,
+}
+
+/// The outcome views don't tell when the receipt has been included,
+/// so we remember the height of the block every receipt was created in
+/// and compare it to the height of the block it is executed in
+#[derive(Debug)]
+pub(crate) struct ReceiptDelayTracker {
+    max_delay_blocks: u64,
+    created_at: HashMap<CryptoHash, BlockHeight>,
+    delay_histogram: BTreeMap<u64, u64>,
+}
+
+impl ReceiptDelayTracker {
+    pub(crate) fn new(max_delay_blocks: u64) -> Self {
+        Self {
+            max_delay_blocks,
+            created_at: HashMap::new(),
+            delay_histogram: BTreeMap::new(),
+        }
+    }
+
+    /// Remembers the receipts produced by a transaction or a receipt outcome
+    pub(crate) fn record_created<'a>(
+        &mut self,
+        receipt_ids: impl IntoIterator<Item = &'a CryptoHash>,
+        block_height: BlockHeight,
+    ) {
+        for receipt_id in receipt_ids {
+            self.created_at.insert(*receipt_id, block_height);
+        }
+    }
+
+    /// Measures the delay of the executed watched receipt, returns the alert
+    /// if it's above the limit
+    pub(crate) fn record_executed(
+        &mut self,
+        receipt_id: &CryptoHash,
+        predecessor_id: &AccountId,
+        receiver_id: &AccountId,
+        block_height: BlockHeight,
+    ) -> Option<ReceiptDelayAlert> {
+        // Receipts created before we've started can't be measured
+        let created_at_block_height = self.created_at.remove(receipt_id)?;
+        let delay_blocks = block_height.saturating_sub(created_at_block_height);
+        *self.delay_histogram.entry(delay_blocks).or_insert(0) += 1;
+        if delay_blocks <= self.max_delay_blocks {
+            return None;
+        }
+        Some(ReceiptDelayAlert {
+            receipt_id: *receipt_id,
+            predecessor_id: predecessor_id.clone(),
+            receiver_id: receiver_id.clone(),
+            created_at_block_height,
+            executed_at_block_height: block_height,
+            delay_blocks,
+            max_delay_blocks: self.max_delay_blocks,
+            delay_histogram: self.delay_histogram.clone(),
+        })
+    }
+
+    /// Drops the receipts that haven't been executed for too long
+    pub(crate) fn forget_stale(&mut self, block_height: BlockHeight) {
+        self.created_at
+            .retain(|_, created_at| block_height.saturating_sub(*created_at) < FORGET_AFTER_BLOCKS);
+    }
+}
@@ -0,0 +1,88 @@
+use borsh::BorshDeserialize;
+
+/// Ref Finance AMM contract on mainnet
+pub(crate) const REF_FINANCE_ACCOUNT: &str = "v2.ref-finance.near";
+
+/// Pools are stored in the `Vector` under the very first storage prefix
+/// of the contract, each pool key is the prefix followed by the pool index (u64 LE)
+const POOLS_PREFIX: u8 = 0;
+
+/// Swap in the pool reconstructed from the pool reserves before and after the change
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct RefPoolEvent {
+    pub pool_id: u64,
+    pub token_in: String,
+    pub amount_in: String,
+    pub token_out: String,
+    pub amount_out: String,
+}
+
+/// The beginning of the `SimplePool` struct of the contract, the fields
+/// after the reserves (volumes, fees, shares) are not needed and not read
+#[derive(BorshDeserialize)]
+struct SimplePoolReserves {
+    token_account_ids: Vec<String>,
+    amounts: Vec<u128>,
+}
+
+pub(crate) struct RefFinanceDecoder;
+
+impl RefFinanceDecoder {
+    /// Returns the pool index if the storage key is the key of a pool
+    pub(crate) fn pool_id(key: &[u8]) -> Option<u64> {
+        match key {
+            [POOLS_PREFIX, index @ ..] if index.len() == 8 => {
+                let mut pool_id = [0u8; 8];
+                pool_id.copy_from_slice(index);
+                Some(u64::from_le_bytes(pool_id))
+            }
+            _ => None,
+        }
+    }
+
+    /// Compares the reserves of the pool before and after the update,
+    /// if one token reserve has grown and another one has shrunk it was a swap
+    pub(crate) fn decode_pool_update(
+        key: &[u8],
+        old: Option<&[u8]>,
+        new: &[u8],
+    ) -> Option<RefPoolEvent> {
+        let pool_id = Self::pool_id(key)?;
+        let old = Self::reserves(old?)?;
+        let new = Self::reserves(new)?;
+        if old.token_account_ids != new.token_account_ids {
+            return None;
+        }
+
+        let mut token_in = None;
+        let mut token_out = None;
+        for (index, (old_amount, new_amount)) in old.amounts.iter().zip(&new.amounts).enumerate() {
+            if new_amount > old_amount {
+                token_in = Some((index, new_amount - old_amount));
+            } else if new_amount < old_amount {
+                token_out = Some((index, old_amount - new_amount));
+            }
+        }
+        let (token_in, amount_in) = token_in?;
+        let (token_out, amount_out) = token_out?;
+
+        Some(RefPoolEvent {
+            pool_id,
+            token_in: new.token_account_ids[token_in].clone(),
+            amount_in: amount_in.to_string(),
+            token_out: new.token_account_ids[token_out].clone(),
+            amount_out: amount_out.to_string(),
+        })
+    }
+
+    fn reserves(value: &[u8]) -> Option<SimplePoolReserves> {
+        // `Pool` is an enum, `SimplePool` is its first variant
+        match value {
+            [0, simple_pool @ ..] => {
+                let mut simple_pool = simple_pool;
+                SimplePoolReserves::deserialize(&mut simple_pool).ok()
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use rand::Rng;
+
+/// Attempts to fetch a block before it is skipped
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Exponential backoff between the attempts to fetch from S3. The random jitter
+/// keeps the watchers deployed together from retrying all at the same moment
+/// after an outage
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub base_ms: u64,
+    pub jitter_ms: u64,
+    /// The cap of the backoff without the jitter
+    pub max_ms: u64,
+    pub multiplier: u32,
+}
+
+impl RetryPolicy {
+    /// The delay after the failed attempt, counting from 0
+    pub(crate) fn next_delay(&self, attempt: u32) -> std::time::Duration {
+        let backoff_ms = u64::from(self.multiplier)
+            .checked_pow(attempt)
+            .and_then(|factor| self.base_ms.checked_mul(factor))
+            .map_or(self.max_ms, |backoff_ms| backoff_ms.min(self.max_ms));
+        let jitter_ms = if self.jitter_ms > 0 {
+            rand::thread_rng().gen_range(0..self.jitter_ms)
+        } else {
+            0
+        };
+        std::time::Duration::from_millis(backoff_ms + jitter_ms)
+    }
+
+    /// Repeats the fetch until it succeeds or the attempts are over.
+    /// A missing object isn't retried, NEAR skips heights
+    pub(crate) async fn run<T, F, Fut>(&self, mut fetch: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match fetch().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt + 1 >= MAX_ATTEMPTS || crate::s3::is_missing_object(&err) => {
+                    return Err(err)
+                }
+                Err(err) => {
+                    let delay = self.next_delay(attempt);
+                    eprintln!(
+                        "Attempt {} failed, retrying in {}ms: {}",
+                        attempt + 1,
+                        delay.as_millis(),
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
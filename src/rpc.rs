@@ -0,0 +1,228 @@
+/// Minimal NEAR JSON-RPC client for the few lookups the Lake data can't answer
+pub(crate) struct JsonRpcClient {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl JsonRpcClient {
+    pub(crate) fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+
+    /// Calls the method and returns the `result` of the response
+    pub(crate) async fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let mut response: serde_json::Value = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "dontcare",
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(error) = response.get_mut("error") {
+            return Err(RpcError {
+                method: method.to_string(),
+                url: self.url.clone(),
+                error: error.take(),
+            }
+            .into());
+        }
+        Ok(response["result"].take())
+    }
+}
+
+/// The `error` the RPC has responded with
+#[derive(Debug)]
+pub(crate) struct RpcError {
+    method: String,
+    url: String,
+    error: serde_json::Value,
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} failed on {}: {}", self.method, self.url, self.error)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Whether the call has failed because there is no block at the height,
+/// e.g. the chain has skipped it
+fn is_unknown_block(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RpcError>(),
+        Some(RpcError { error, .. }) if error["cause"]["name"] == "UNKNOWN_BLOCK"
+    )
+}
+
+/// Looks up the height of the first block of the epoch N. No RPC method takes
+/// the epoch number, and the validators of a past epoch are only available at
+/// its last block, so we step back one epoch at a time from the current one.
+/// That takes two calls per epoch, fine for testnet or a private network
+/// but slow for the early epochs of mainnet
+pub(crate) async fn find_epoch_start_height(
+    rpc: &JsonRpcClient,
+    epoch_height: u64,
+) -> anyhow::Result<u64> {
+    let mut validators = rpc.call("validators", serde_json::json!([null])).await?;
+    loop {
+        let current_epoch_height = validators["epoch_height"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("validators response has no epoch_height"))?;
+        let epoch_start_height = validators["epoch_start_height"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("validators response has no epoch_start_height"))?;
+        if current_epoch_height == epoch_height {
+            return Ok(epoch_start_height);
+        }
+        if current_epoch_height < epoch_height {
+            anyhow::bail!(
+                "Epoch {} hasn't started yet, the current one is {}",
+                epoch_height,
+                current_epoch_height
+            );
+        }
+        // The previous block of the epoch start is the last block of the previous epoch
+        let epoch_start_block = rpc
+            .call(
+                "block",
+                serde_json::json!({ "block_id": epoch_start_height }),
+            )
+            .await?;
+        let last_block_hash = epoch_start_block["header"]["prev_hash"].clone();
+        validators = rpc
+            .call("validators", serde_json::json!([last_block_hash]))
+            .await?;
+    }
+}
+
+/// Finds the first block produced at or after the timestamp (in nanoseconds)
+/// with a binary search over the heights from the genesis to the latest final block
+pub(crate) async fn find_block_by_timestamp(rpc_url: &str, target_ts: u64) -> anyhow::Result<u64> {
+    let rpc = JsonRpcClient::new(rpc_url);
+    let genesis_config = rpc
+        .call("EXPERIMENTAL_genesis_config", serde_json::json!({}))
+        .await?;
+    let mut low = genesis_config["genesis_height"]
+        .as_u64()
+        .ok_or_else(|| anyhow::anyhow!("Genesis config has no genesis_height"))?;
+    let latest_block = rpc
+        .call("block", serde_json::json!({ "finality": "final" }))
+        .await?;
+    let (mut high, latest_ts) = block_height_and_timestamp(&latest_block)?;
+    if latest_ts < target_ts {
+        anyhow::bail!(
+            "The latest final block #{} is older than the given time",
+            high
+        );
+    }
+
+    let mut blocks = BlockTimestampCache::default();
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let (_, ts) = blocks.at_or_after(&rpc, middle, high).await?;
+        if ts >= target_ts {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    let (height, _) = blocks.at_or_after(&rpc, low, u64::MAX).await?;
+    Ok(height)
+}
+
+fn block_height_and_timestamp(block: &serde_json::Value) -> anyhow::Result<(u64, u64)> {
+    let height = block["header"]["height"]
+        .as_u64()
+        .ok_or_else(|| anyhow::anyhow!("Block has no height"))?;
+    let timestamp = block["header"]["timestamp"]
+        .as_u64()
+        .ok_or_else(|| anyhow::anyhow!("Block #{} has no timestamp", height))?;
+    Ok((height, timestamp))
+}
+
+/// The blocks the binary search has already seen. NEAR skips heights,
+/// so each entry is the first block at or after the height
+#[derive(Default)]
+struct BlockTimestampCache {
+    blocks: std::collections::HashMap<u64, (u64, u64)>,
+}
+
+impl BlockTimestampCache {
+    /// Returns the height and the timestamp of the first block at or after the height,
+    /// `limit` is the height we know the block exists at
+    async fn at_or_after(
+        &mut self,
+        rpc: &JsonRpcClient,
+        height: u64,
+        limit: u64,
+    ) -> anyhow::Result<(u64, u64)> {
+        if let Some(block) = self.blocks.get(&height) {
+            return Ok(*block);
+        }
+        let mut candidate = height;
+        let block = loop {
+            match rpc
+                .call("block", serde_json::json!({ "block_id": candidate }))
+                .await
+            {
+                Ok(block) => break block_height_and_timestamp(&block)?,
+                // The height was skipped, try the next one
+                Err(err) if is_unknown_block(&err) && candidate < limit => candidate += 1,
+                Err(err) => return Err(err),
+            }
+        };
+        self.blocks.insert(height, block);
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc_error(error: serde_json::Value) -> anyhow::Error {
+        RpcError {
+            method: "block".to_string(),
+            url: "https://archival-rpc.mainnet.near.org".to_string(),
+            error,
+        }
+        .into()
+    }
+
+    #[test]
+    fn recognizes_unknown_block() {
+        assert!(is_unknown_block(&rpc_error(serde_json::json!({
+            "name": "HANDLER_ERROR",
+            "cause": {"name": "UNKNOWN_BLOCK", "info": {}},
+            "code": -32000,
+        }))));
+    }
+
+    #[test]
+    fn other_errors_are_not_unknown_block() {
+        assert!(!is_unknown_block(&rpc_error(serde_json::json!({
+            "name": "HANDLER_ERROR",
+            "cause": {"name": "GARBAGE_COLLECTED_BLOCK", "info": {}},
+        }))));
+        assert!(!is_unknown_block(&rpc_error(serde_json::json!({
+            "name": "REQUEST_VALIDATION_ERROR",
+            "cause": {"name": "PARSE_ERROR", "info": {}},
+        }))));
+        assert!(!is_unknown_block(&anyhow::anyhow!("Connection refused")));
+    }
+}
@@ -0,0 +1,210 @@
+use aws_sdk_s3::presigning::config::PresigningConfig;
+use aws_sdk_s3::Client;
+use near_lake_framework::near_indexer_primitives::types::{BlockHeight, ShardId};
+use near_lake_framework::near_indexer_primitives::views::BlockView;
+use near_lake_framework::near_indexer_primitives::IndexerShard;
+
+use crate::configs::{ChainId, Opts};
+
+/// Creates the S3 client the same way the NEAR Lake Framework does it,
+/// so we can fetch the objects from the bucket directly
+pub(crate) async fn client(opts: &Opts) -> Client {
+    match opts.s3_config().await {
+        Some(s3_config) => Client::from_conf(s3_config),
+        None => default_client(opts.chain_id()).await,
+    }
+}
+
+/// Creates the S3 client with the credentials from the environment
+pub(crate) async fn default_client(chain_id: &ChainId) -> Client {
+    let aws_config = aws_config::from_env().load().await;
+    let mut s3_config = aws_sdk_s3::config::Builder::from(&aws_config).region(
+        aws_types::region::Region::new(chain_id.s3_region_name().to_string()),
+    );
+    if let Some(endpoint) = chain_id.s3_endpoint() {
+        s3_config = s3_config.endpoint_resolver(aws_sdk_s3::Endpoint::immutable(endpoint.clone()));
+    }
+    Client::from_conf(s3_config.build())
+}
+
+/// Fetches the `block.json` object of the given block height from the bucket
+pub(crate) async fn fetch_block(
+    client: &Client,
+    s3_bucket_name: &str,
+    block_height: BlockHeight,
+    verify_checksum: bool,
+) -> anyhow::Result<BlockView> {
+    let body_bytes = fetch_object(
+        client,
+        s3_bucket_name,
+        format!("{:0>12}/block.json", block_height),
+        verify_checksum,
+    )
+    .await?;
+    Ok(serde_json::from_slice::<BlockView>(&body_bytes)?)
+}
+
+/// Returns the first block height at or after the given one the bucket has,
+/// NEAR skips heights, so the exact one may not be there
+pub(crate) async fn first_block_height_from(
+    client: &Client,
+    s3_bucket_name: &str,
+    block_height: BlockHeight,
+) -> anyhow::Result<Option<BlockHeight>> {
+    let response = client
+        .list_objects_v2()
+        .bucket(s3_bucket_name)
+        .delimiter("/")
+        // `000000000010/block.json` comes after `000000000010`,
+        // so the block itself isn't skipped
+        .start_after(format!("{:0>12}", block_height))
+        .max_keys(1)
+        .request_payer(aws_sdk_s3::model::RequestPayer::Requester)
+        .send()
+        .await?;
+    let prefix = response
+        .common_prefixes
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|common_prefix| common_prefix.prefix);
+    match prefix {
+        Some(prefix) => Ok(Some(prefix.trim_end_matches('/').parse()?)),
+        None => Ok(None),
+    }
+}
+
+/// Fetches the `shard_<shard_id>.json` object of the given block height from the bucket
+pub(crate) async fn fetch_shard(
+    client: &Client,
+    s3_bucket_name: &str,
+    block_height: BlockHeight,
+    shard_id: ShardId,
+    verify_checksum: bool,
+) -> anyhow::Result<IndexerShard> {
+    let body_bytes = fetch_object(
+        client,
+        s3_bucket_name,
+        format!("{:0>12}/shard_{}.json", block_height, shard_id),
+        verify_checksum,
+    )
+    .await?;
+    Ok(serde_json::from_slice::<IndexerShard>(&body_bytes)?)
+}
+
+/// Whether the fetch has failed because there is no such object in the bucket
+pub(crate) fn is_missing_object(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<aws_sdk_s3::types::SdkError<aws_sdk_s3::error::GetObjectError>>(),
+        Some(aws_sdk_s3::types::SdkError::ServiceError { err, .. }) if err.is_no_such_key()
+    )
+}
+
+/// The object we have read doesn't hash to its ETag (used by `--verify-checksums`)
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ChecksumMismatch {
+    pub key: String,
+    pub etag: String,
+    pub md5: String,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} has MD5 {} but its ETag is {}",
+            self.key, self.md5, self.etag
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+async fn fetch_object(
+    client: &Client,
+    s3_bucket_name: &str,
+    key: String,
+    verify_checksum: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let response = client
+        .get_object()
+        .bucket(s3_bucket_name)
+        .key(&key)
+        .request_payer(aws_sdk_s3::model::RequestPayer::Requester)
+        .send()
+        .await?;
+    let etag = response.e_tag.clone();
+    let body_bytes = response.body.collect().await?.into_bytes().to_vec();
+    if verify_checksum {
+        // The ETag is the MD5 of the content only for the objects uploaded in one part,
+        // the multipart ETags have the `-<number of parts>` suffix
+        let etag = etag.as_deref().unwrap_or_default().trim_matches('"');
+        if !etag.is_empty() && !etag.contains('-') {
+            let md5 = format!("{:x}", md5::compute(&body_bytes));
+            if md5 != etag {
+                return Err(ChecksumMismatch {
+                    key,
+                    etag: etag.to_string(),
+                    md5,
+                }
+                .into());
+            }
+        }
+    }
+    Ok(body_bytes)
+}
+
+/// Creates pre-signed URLs of the NEAR Lake objects, so the blocks can be shared
+/// with people who don't have AWS credentials
+pub(crate) struct Presigner {
+    client: Client,
+    s3_bucket_name: String,
+    expires_in: std::time::Duration,
+}
+
+impl Presigner {
+    pub(crate) async fn new(
+        opts: &Opts,
+        s3_bucket_name: &str,
+        expires_in: std::time::Duration,
+    ) -> anyhow::Result<Self> {
+        // The expiration is checked once here, so we don't fail on every block later
+        PresigningConfig::expires_in(expires_in)
+            .map_err(|err| anyhow::anyhow!("Invalid --presign-matched-blocks: {}", err))?;
+        Ok(Self {
+            client: client(opts).await,
+            s3_bucket_name: s3_bucket_name.to_string(),
+            expires_in,
+        })
+    }
+
+    /// The URL of the `block.json` object of the given block height
+    pub(crate) async fn block_url(&self, block_height: BlockHeight) -> anyhow::Result<String> {
+        self.presign(format!("{:0>12}/block.json", block_height))
+            .await
+    }
+
+    /// The URL of the `shard_<shard_id>.json` object of the given block height
+    pub(crate) async fn shard_url(
+        &self,
+        block_height: BlockHeight,
+        shard_id: ShardId,
+    ) -> anyhow::Result<String> {
+        self.presign(format!("{:0>12}/shard_{}.json", block_height, shard_id))
+            .await
+    }
+
+    async fn presign(&self, key: String) -> anyhow::Result<String> {
+        // The URL expires relative to the moment the config is created,
+        // so we need a fresh one for every URL
+        let presigning_config = PresigningConfig::expires_in(self.expires_in)?;
+        let presigned_request = self
+            .client
+            .get_object()
+            .bucket(&self.s3_bucket_name)
+            .key(key)
+            .request_payer(aws_sdk_s3::model::RequestPayer::Requester)
+            .presigned(presigning_config)
+            .await?;
+        Ok(presigned_request.uri().to_string())
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+
+use near_lake_framework::near_indexer_primitives::types::{BlockHeight, ShardId};
+
+/// How long it took to handle the shard and how much data it had
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub(crate) struct ShardStats {
+    pub shard_id: ShardId,
+    pub processing_time_us: u128,
+    pub total_state_changes: u64,
+    pub matched_changes: u64,
+    pub receipt_count: u64,
+}
+
+/// Per-shard totals over several blocks (used by `--shard-stats-summary-interval-blocks`)
+#[derive(Debug, Default)]
+pub(crate) struct ShardStatsSummary {
+    pub first_block_height: Option<BlockHeight>,
+    pub blocks: u64,
+    pub shards: BTreeMap<ShardId, ShardStats>,
+}
+
+impl ShardStatsSummary {
+    pub(crate) fn add(&mut self, block_height: BlockHeight, block_stats: &[ShardStats]) {
+        self.first_block_height.get_or_insert(block_height);
+        self.blocks += 1;
+        for shard_stats in block_stats {
+            let summary = self
+                .shards
+                .entry(shard_stats.shard_id)
+                .or_insert_with(|| ShardStats {
+                    shard_id: shard_stats.shard_id,
+                    ..ShardStats::default()
+                });
+            summary.processing_time_us += shard_stats.processing_time_us;
+            summary.total_state_changes += shard_stats.total_state_changes;
+            summary.matched_changes += shard_stats.matched_changes;
+            summary.receipt_count += shard_stats.receipt_count;
+        }
+    }
+
+    /// Returns the summary JSON and starts counting from scratch
+    pub(crate) fn take(&mut self, last_block_height: BlockHeight) -> serde_json::Value {
+        let summary = std::mem::take(self);
+        serde_json::json!({
+            "from_block_height": summary.first_block_height,
+            "to_block_height": last_block_height,
+            "blocks": summary.blocks,
+            "shards": summary.shards.into_values().collect::<Vec<ShardStats>>(),
+        })
+    }
+}
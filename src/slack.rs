@@ -0,0 +1,85 @@
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// Slack allows a single message per second for an incoming webhook
+const POST_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Messages waiting to be posted, the newer ones are dropped when it's full
+const QUEUE_SIZE: usize = 1000;
+/// Slack limit for the text of a context block element
+const MAX_TEXT_CHARS: usize = 3000;
+
+/// Posts the matched changes and the critical alerts to the `--alert-slack-webhook`
+/// as Block Kit messages, one per second from a background task
+pub(crate) struct Slack {
+    sender: tokio::sync::mpsc::Sender<serde_json::Value>,
+    mention_user: Option<String>,
+}
+
+impl Slack {
+    pub(crate) fn new(url: String, mention_user: Option<String>) -> Self {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(QUEUE_SIZE);
+        let client = reqwest::Client::new();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if let Err(err) = client
+                    .post(&url)
+                    .json(&message)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                {
+                    eprintln!("Failed to send the alert to Slack: {}", err);
+                }
+                tokio::time::sleep(POST_INTERVAL).await;
+            }
+        });
+        Self {
+            sender,
+            mention_user,
+        }
+    }
+
+    /// Queues the message, the `--slack-mention-user` is mentioned in the critical ones
+    pub(crate) fn send(
+        &self,
+        block_height: BlockHeight,
+        account_id: &AccountId,
+        change_type: &str,
+        event: &serde_json::Value,
+        is_critical: bool,
+    ) {
+        let title = format!("#{} {} {}", block_height, account_id, change_type);
+        let mut blocks = vec![serde_json::json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": title },
+        })];
+        if let Some(mention_user) = self.mention_user.as_ref().filter(|_| is_critical) {
+            blocks.push(serde_json::json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("<@{}> critical alert", mention_user) },
+            }));
+        }
+        blocks.push(serde_json::json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": code_snippet(event) }],
+        }));
+        let message = serde_json::json!({ "text": title, "blocks": blocks });
+        if self.sender.try_send(message).is_err() {
+            eprintln!(
+                "Slack queue is full, dropping the message about #{}",
+                block_height
+            );
+        }
+    }
+}
+
+/// Formats the JSON as a code snippet that fits into the Slack text limit
+fn code_snippet(event: &serde_json::Value) -> String {
+    let json = serde_json::to_string_pretty(event).expect("Failed to serialize event to JSON");
+    // Leave room for the backticks and the ellipsis
+    let max_chars = MAX_TEXT_CHARS - 10;
+    let mut snippet: String = json.chars().take(max_chars).collect();
+    if snippet.len() < json.len() {
+        snippet.push('…');
+    }
+    format!("```{}```", snippet)
+}
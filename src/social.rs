@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+use near_lake_framework::near_indexer_primitives::views::StateChangeValueView;
+
+/// Number of characters of the widget source we print
+const CODE_PREVIEW_CHARS: usize = 100;
+
+/// The contract keeps its data as a tree of nodes, the children of each node
+/// are an `UnorderedMap<String, NodeValue>` under the `StorageKey::Node { node_id }` prefix
+/// ref https://github.com/NearSocial/social-db/blob/master/contract/src/node.rs
+const NODE_STORAGE_PREFIX: u8 = 2;
+/// The children of the root node are the accounts
+const ROOT_NODE_ID: NodeId = 0;
+/// The `UnorderedMap` of near-sdk keeps the index of each key under `i`,
+/// the keys under `k` and the values under `v`
+const INDEX_PREFIX: u8 = b'i';
+const KEY_PREFIX: u8 = b'k';
+const VALUE_PREFIX: u8 = b'v';
+
+type NodeId = u32;
+
+/// Near.Social follow relationship change
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct SocialFollowEvent {
+    pub follower: AccountId,
+    pub followed: AccountId,
+    pub block_height: BlockHeight,
+}
+
+/// Near.Social widget source update
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct WidgetEvent {
+    pub author: AccountId,
+    pub widget_name: String,
+    pub code_size_bytes: usize,
+    pub code_preview: String,
+    pub block_height: BlockHeight,
+}
+
+/// The storage key of a child of a node
+#[derive(Debug, PartialEq)]
+enum NodeKey {
+    Index { node_id: NodeId, name: String },
+    Name { node_id: NodeId, index: u64 },
+    Value { node_id: NodeId, index: u64 },
+}
+
+/// The `NodeValue` of the contract
+#[derive(Debug, PartialEq)]
+enum NodeValue {
+    Node(NodeId),
+    Value(String),
+    /// The contract keeps the `null`s set by the users as deleted entries
+    DeletedEntry,
+}
+
+fn parse_node_key(key: &[u8]) -> Option<NodeKey> {
+    let (&prefix, key) = key.split_first()?;
+    if prefix != NODE_STORAGE_PREFIX || key.len() < 5 {
+        return None;
+    }
+    let node_id = NodeId::try_from_slice(&key[..4]).ok()?;
+    let entry = &key[5..];
+    match key[4] {
+        INDEX_PREFIX => Some(NodeKey::Index {
+            node_id,
+            name: String::try_from_slice(entry).ok()?,
+        }),
+        KEY_PREFIX => Some(NodeKey::Name {
+            node_id,
+            index: u64::try_from_slice(entry).ok()?,
+        }),
+        VALUE_PREFIX => Some(NodeKey::Value {
+            node_id,
+            index: u64::try_from_slice(entry).ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// The values are stored along with the height they were set at,
+/// so we read only the beginning of them
+fn parse_node_value(value: &[u8]) -> Option<NodeValue> {
+    let (&variant, mut value) = value.split_first()?;
+    match variant {
+        0 => Some(NodeValue::Node(NodeId::deserialize(&mut value).ok()?)),
+        1 => Some(NodeValue::Value(String::deserialize(&mut value).ok()?)),
+        2 => Some(NodeValue::DeletedEntry),
+        _ => None,
+    }
+}
+
+/// Rebuilds the paths of the Near.Social data from the storage of the contract.
+/// A key is stored only once when it's added to a node, so we can decode the keys
+/// added since the watcher has started and the values set under them
+#[derive(Debug, Default)]
+pub(crate) struct SocialDbDecoder {
+    /// The key of each child of the nodes
+    names: HashMap<(NodeId, u64), String>,
+    /// The parent of each node and the index of the node among the children
+    parents: HashMap<NodeId, (NodeId, u64)>,
+}
+
+impl SocialDbDecoder {
+    /// Remembers the keys and the nodes added by the StateChange of the contract,
+    /// the entries of one block refer to each other, so all of them
+    /// have to be learned before any of them is decoded
+    pub(crate) fn learn(&mut self, value: &StateChangeValueView) {
+        let (key, value) = match value {
+            StateChangeValueView::DataUpdate { key, value, .. } => (key, value),
+            _ => return,
+        };
+        let (key, value): (&[u8], &[u8]) = (key.as_ref(), value.as_ref());
+        match parse_node_key(key) {
+            Some(NodeKey::Index { node_id, name }) => {
+                if let Ok(index) = u64::try_from_slice(value) {
+                    self.names.insert((node_id, index), name);
+                }
+            }
+            Some(NodeKey::Name { node_id, index }) => {
+                if let Ok(name) = String::try_from_slice(value) {
+                    self.names.insert((node_id, index), name);
+                }
+            }
+            Some(NodeKey::Value { node_id, index }) => {
+                if let Some(NodeValue::Node(child_node_id)) = parse_node_value(value) {
+                    self.parents.insert(child_node_id, (node_id, index));
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the path of the value stored under the key,
+    /// e.g. `["alice.near", "graph", "follow", "bob.near"]`
+    fn parse_key(&self, key: &[u8]) -> Option<Vec<&str>> {
+        let (mut node_id, index) = match parse_node_key(key)? {
+            NodeKey::Value { node_id, index } => (node_id, index),
+            _ => return None,
+        };
+        let mut path = vec![self.names.get(&(node_id, index))?.as_str()];
+        while node_id != ROOT_NODE_ID {
+            // The tree has no cycles, unless we have misread a node
+            if path.len() > self.parents.len() {
+                return None;
+            }
+            let (parent_node_id, index) = *self.parents.get(&node_id)?;
+            path.push(self.names.get(&(parent_node_id, index))?.as_str());
+            node_id = parent_node_id;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the path and the value set by the StateChange of the contract
+    fn entry(&self, value: &StateChangeValueView) -> Option<(Vec<&str>, NodeValue)> {
+        match value {
+            StateChangeValueView::DataUpdate { key, value, .. } => Some((
+                self.parse_key(key.as_ref())?,
+                parse_node_value(value.as_ref())?,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the widget deployments from the storage of the Near.Social contract
+pub(crate) struct SocialWidgetDecoder<'a> {
+    social_db: &'a SocialDbDecoder,
+}
+
+impl<'a> SocialWidgetDecoder<'a> {
+    pub(crate) fn new(social_db: &'a SocialDbDecoder) -> Self {
+        Self { social_db }
+    }
+
+    /// Returns the widget whose source is stored under the key, either
+    /// `<author>/widget/<name>` or `<author>/widget/<name>/""` for the widgets with
+    /// metadata. The source and the height are filled in by `widget_event`
+    pub(crate) fn parse_key(&self, key: &[u8]) -> Option<WidgetEvent> {
+        let (author, widget_name) = match self.social_db.parse_key(key)?.as_slice() {
+            [author, "widget", widget_name] | [author, "widget", widget_name, ""] => {
+                (author.parse().ok()?, widget_name.to_string())
+            }
+            _ => return None,
+        };
+        if widget_name.is_empty() {
+            return None;
+        }
+        Some(WidgetEvent {
+            author,
+            widget_name,
+            code_size_bytes: 0,
+            code_preview: String::new(),
+            block_height: 0,
+        })
+    }
+
+    /// Checks whether the StateChange of the Near.Social contract
+    /// is a widget deployment
+    pub(crate) fn widget_event(
+        &self,
+        value: &StateChangeValueView,
+        block_height: BlockHeight,
+    ) -> Option<WidgetEvent> {
+        let (key, value) = match value {
+            StateChangeValueView::DataUpdate { key, value, .. } => (key, value),
+            _ => return None,
+        };
+        let code = match parse_node_value(value.as_ref())? {
+            NodeValue::Value(code) => code,
+            _ => return None,
+        };
+        Some(WidgetEvent {
+            code_size_bytes: code.len(),
+            code_preview: code.chars().take(CODE_PREVIEW_CHARS).collect(),
+            block_height,
+            ..self.parse_key(key.as_ref())?
+        })
+    }
+}
+
+/// Checks whether the StateChange of the Near.Social contract sets a
+/// `<follower>/graph/follow/<followed>` value (a follow) or a `null` (an unfollow)
+/// and returns the event type along with the event
+pub(crate) fn follow_event(
+    social_db: &SocialDbDecoder,
+    value: &StateChangeValueView,
+    block_height: BlockHeight,
+) -> Option<(&'static str, SocialFollowEvent)> {
+    let (path, value) = social_db.entry(value)?;
+    let (follower, followed) = match path.as_slice() {
+        [follower, "graph", "follow", followed] => (follower.parse().ok()?, followed.parse().ok()?),
+        _ => return None,
+    };
+    let event_type = match value {
+        NodeValue::Value(_) => "social_follow",
+        NodeValue::DeletedEntry => "social_unfollow",
+        NodeValue::Node(_) => return None,
+    };
+    Some((
+        event_type,
+        SocialFollowEvent {
+            follower,
+            followed,
+            block_height,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+
+    use super::*;
+
+    fn node_key(node_id: NodeId, prefix: u8, entry: &[u8]) -> Vec<u8> {
+        let mut key = vec![NODE_STORAGE_PREFIX];
+        key.extend_from_slice(&node_id.to_le_bytes());
+        key.push(prefix);
+        key.extend_from_slice(entry);
+        key
+    }
+
+    fn data_update(key: Vec<u8>, value: Vec<u8>) -> StateChangeValueView {
+        StateChangeValueView::DataUpdate {
+            account_id: "social.near".parse().unwrap(),
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    fn value(value: Option<&str>) -> Vec<u8> {
+        match value {
+            // `Value(ValueAtHeight { value, block_height })`
+            Some(value) => {
+                let mut node_value = vec![1];
+                value.serialize(&mut node_value).unwrap();
+                100u64.serialize(&mut node_value).unwrap();
+                node_value
+            }
+            // `DeletedEntry(block_height)`
+            None => {
+                let mut node_value = vec![2];
+                100u64.serialize(&mut node_value).unwrap();
+                node_value
+            }
+        }
+    }
+
+    fn node(node_id: NodeId) -> Vec<u8> {
+        let mut node_value = vec![0];
+        node_id.serialize(&mut node_value).unwrap();
+        node_value
+    }
+
+    /// The DataUpdates the contract makes to add the `name` child
+    /// at `index` to the node, the index of the key comes first
+    fn add_child(
+        node_id: NodeId,
+        index: u64,
+        name: &str,
+        node_value: Vec<u8>,
+    ) -> Vec<StateChangeValueView> {
+        let index_bytes = index.try_to_vec().unwrap();
+        vec![
+            data_update(
+                node_key(node_id, INDEX_PREFIX, &name.try_to_vec().unwrap()),
+                index_bytes.clone(),
+            ),
+            data_update(
+                node_key(node_id, KEY_PREFIX, &index_bytes),
+                name.try_to_vec().unwrap(),
+            ),
+            data_update(node_key(node_id, VALUE_PREFIX, &index_bytes), node_value),
+        ]
+    }
+
+    /// `set({"data": {"alice.near": {"graph": {"follow": {"bob.near": ""}}}}})`
+    /// on an empty contract, the changes come sorted by key
+    fn first_follow() -> Vec<StateChangeValueView> {
+        let mut changes = add_child(ROOT_NODE_ID, 0, "alice.near", node(1));
+        changes.extend(add_child(1, 0, "graph", node(2)));
+        changes.extend(add_child(2, 0, "follow", node(3)));
+        changes.extend(add_child(3, 0, "bob.near", value(Some(""))));
+        changes.reverse();
+        changes
+    }
+
+    fn decoder(changes: &[StateChangeValueView]) -> SocialDbDecoder {
+        let mut social_db = SocialDbDecoder::default();
+        for change in changes {
+            social_db.learn(change);
+        }
+        social_db
+    }
+
+    #[test]
+    fn decodes_follows_and_unfollows() {
+        let changes = first_follow();
+        let social_db = decoder(&changes);
+        let events: Vec<_> = changes
+            .iter()
+            .filter_map(|change| follow_event(&social_db, change, 100))
+            .collect();
+        assert_eq!(events.len(), 1);
+        let (event_type, event) = &events[0];
+        assert_eq!(*event_type, "social_follow");
+        assert_eq!(event.follower.as_ref(), "alice.near");
+        assert_eq!(event.followed.as_ref(), "bob.near");
+
+        // `{"bob.near": null}` only replaces the value of the known key
+        let unfollow = data_update(node_key(3, VALUE_PREFIX, &0u64.to_le_bytes()), value(None));
+        let (event_type, event) = follow_event(&social_db, &unfollow, 101).unwrap();
+        assert_eq!(event_type, "social_unfollow");
+        assert_eq!(event.followed.as_ref(), "bob.near");
+        assert_eq!(event.block_height, 101);
+
+        // The key of the other entries of the follow node is unknown
+        let unknown = data_update(
+            node_key(3, VALUE_PREFIX, &1u64.to_le_bytes()),
+            value(Some("")),
+        );
+        assert!(follow_event(&social_db, &unknown, 101).is_none());
+    }
+
+    #[test]
+    fn decodes_widget_sources() {
+        let mut social_db = decoder(&first_follow());
+        let widget_changes: Vec<_> = [
+            add_child(1, 1, "widget", node(4)),
+            add_child(4, 0, "Feed", value(Some("return <div>Feed</div>;"))),
+            add_child(4, 1, "Profile", node(5)),
+            add_child(5, 0, "", value(Some("return <Profile />;"))),
+            add_child(5, 1, "metadata", node(6)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        for change in &widget_changes {
+            social_db.learn(change);
+        }
+        let widget_decoder = SocialWidgetDecoder::new(&social_db);
+        let widgets: Vec<_> = widget_changes
+            .iter()
+            .filter_map(|change| widget_decoder.widget_event(change, 100))
+            .map(|event| {
+                (
+                    event.author.to_string(),
+                    event.widget_name,
+                    event.code_size_bytes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            widgets,
+            [
+                ("alice.near".to_string(), "Feed".to_string(), 23),
+                ("alice.near".to_string(), "Profile".to_string(), 19),
+            ]
+        );
+
+        let key = node_key(4, VALUE_PREFIX, &0u64.to_le_bytes());
+        let event = widget_decoder.parse_key(&key).unwrap();
+        assert_eq!(event.widget_name, "Feed");
+        // The follow isn't a widget
+        let key = node_key(3, VALUE_PREFIX, &0u64.to_le_bytes());
+        assert!(widget_decoder.parse_key(&key).is_none());
+    }
+
+    #[test]
+    fn parses_node_keys() {
+        assert_eq!(
+            parse_node_key(&node_key(7, INDEX_PREFIX, &"graph".try_to_vec().unwrap())),
+            Some(NodeKey::Index {
+                node_id: 7,
+                name: "graph".to_string()
+            })
+        );
+        assert_eq!(
+            parse_node_key(&node_key(7, VALUE_PREFIX, &3u64.to_le_bytes())),
+            Some(NodeKey::Value {
+                node_id: 7,
+                index: 3
+            })
+        );
+        // The accounts and the other collections of the contract
+        assert_eq!(parse_node_key(b"\x00alice.near"), None);
+        assert_eq!(parse_node_key(&node_key(7, VALUE_PREFIX, &[3])), None);
+        assert_eq!(parse_node_value(&node(5)), Some(NodeValue::Node(5)));
+        assert_eq!(
+            parse_node_value(&value(None)),
+            Some(NodeValue::DeletedEntry)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use borsh::BorshDeserialize;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+/// Accounts deployed by the staking pool factories on mainnet and testnet
+const STAKING_POOL_SUFFIXES: &[&str] = &[".pool.near", ".poolv1.near", ".pool.f863973.m0"];
+
+/// The contract struct is stored under this key by near-sdk
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Delegator action on a staking pool
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct StakingPoolEvent {
+    #[serde(skip)]
+    pub event_type: &'static str,
+    pub pool_id: AccountId,
+    pub delegator_id: String,
+    pub amount_yocto: String,
+    pub total_staked_shares: String,
+    pub block_height: BlockHeight,
+}
+
+/// The beginning of the `StakingContract` struct of the reference staking pool
+/// ref https://github.com/near/core-contracts/blob/master/staking-pool/src/lib.rs
+/// the fields after the shares (reward fee, accounts map, paused) are not read
+#[derive(BorshDeserialize)]
+struct StakingContractState {
+    _owner_id: String,
+    _stake_public_key: Vec<u8>,
+    _last_epoch_height: u64,
+    _last_total_balance: u128,
+    _total_staked_balance: u128,
+    total_stake_shares: u128,
+}
+
+pub(crate) struct StakingPoolDecoder;
+
+impl StakingPoolDecoder {
+    pub(crate) fn is_staking_pool(account_id: &AccountId) -> bool {
+        STAKING_POOL_SUFFIXES
+            .iter()
+            .any(|suffix| account_id.as_ref().ends_with(suffix))
+    }
+
+    /// Returns the total number of stake shares if the key is the contract state.
+    /// The state is updated by every delegator action, while the delegator accounts
+    /// are stored by their index in the accounts map and don't tell who the delegator is
+    pub(crate) fn total_stake_shares(key: &[u8], value: &[u8]) -> Option<u128> {
+        if key != STATE_KEY {
+            return None;
+        }
+        let mut value = value;
+        StakingContractState::deserialize(&mut value)
+            .ok()
+            .map(|state| state.total_stake_shares)
+    }
+
+    /// Recognizes the delegator actions in the logs of the receipt that updated the state:
+    /// - `@{delegator} deposited {amount}. New unstaked balance is {balance}`
+    /// - `@{delegator} staking {amount}. Received {shares} new staking shares. ...`
+    /// - `@{delegator} unstaking {amount}. Spent {shares} staking shares. ...`
+    /// - `@{delegator} withdrawing {amount}. New unstaked balance is {balance}`
+    pub(crate) fn decode_log(
+        log: &str,
+        pool_id: &AccountId,
+        total_stake_shares: u128,
+        block_height: BlockHeight,
+    ) -> Option<StakingPoolEvent> {
+        let mut words = log.split_whitespace();
+        let delegator_id = words.next()?.strip_prefix('@')?;
+        let event_type = match words.next()? {
+            "deposited" => "staking_pool_delegate",
+            "staking" => "staking_pool_stake",
+            "unstaking" => "staking_pool_undelegate",
+            "withdrawing" => "staking_pool_withdraw",
+            _ => return None,
+        };
+        let amount = words.next()?.trim_end_matches('.').parse::<u128>().ok()?;
+        Some(StakingPoolEvent {
+            event_type,
+            pool_id: pool_id.clone(),
+            delegator_id: delegator_id.to_string(),
+            amount_yocto: amount.to_string(),
+            total_staked_shares: total_stake_shares.to_string(),
+            block_height,
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight, StorageUsage};
+
+/// The account's storage usage has reached the threshold
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct AlertEvent {
+    pub account_id: AccountId,
+    pub storage_usage: StorageUsage,
+    pub threshold: StorageUsage,
+    pub block_height: BlockHeight,
+}
+
+/// Fires once when the storage usage of an account crosses the threshold and
+/// stays quiet until the usage drops below the reset level, so an account
+/// hovering around the limit doesn't flood us with alerts
+#[derive(Debug)]
+pub(crate) struct StorageAlertTracker {
+    threshold: StorageUsage,
+    reset_below: StorageUsage,
+    /// Whether the account is above the threshold (alerted already)
+    /// as of its last `AccountUpdate`
+    alerted: HashMap<AccountId, bool>,
+}
+
+impl StorageAlertTracker {
+    pub(crate) fn new(threshold: StorageUsage, hysteresis_pct: u8) -> Self {
+        Self {
+            threshold,
+            reset_below: threshold - threshold * u64::from(hysteresis_pct.min(100)) / 100,
+            alerted: HashMap::new(),
+        }
+    }
+
+    /// Returns the alert if the account has crossed the threshold since its
+    /// previous `AccountUpdate`. The first update of an account only sets the
+    /// state, as we don't know where the usage was before
+    pub(crate) fn check(
+        &mut self,
+        account_id: &AccountId,
+        storage_usage: StorageUsage,
+        block_height: BlockHeight,
+    ) -> Option<AlertEvent> {
+        let is_above = storage_usage >= self.threshold;
+        let alerted = match self.alerted.get_mut(account_id) {
+            Some(alerted) => alerted,
+            None => {
+                self.alerted.insert(account_id.clone(), is_above);
+                return None;
+            }
+        };
+        if *alerted {
+            if storage_usage < self.reset_below {
+                *alerted = false;
+            }
+            return None;
+        }
+        if !is_above {
+            return None;
+        }
+        *alerted = true;
+        Some(AlertEvent {
+            account_id: account_id.clone(),
+            storage_usage,
+            threshold: self.threshold,
+            block_height,
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+use near_lake_framework::near_indexer_primitives::views::{ActionView, ReceiptEnumView};
+use near_lake_framework::near_indexer_primitives::{
+    CryptoHash, IndexerExecutionOutcomeWithReceipt,
+};
+
+/// One function call the pattern waits for
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct TxPatternStep {
+    pub receiver: AccountId,
+    pub method: String,
+}
+
+/// Sequence of function calls passed with `--tx-pattern`, e.g.
+/// `[{"receiver": "token.near", "method": "ft_transfer_call"},
+///   {"receiver": "app.near", "method": "ft_on_transfer"}]`
+#[derive(Debug, serde::Serialize)]
+#[serde(transparent)]
+pub(crate) struct TxPattern {
+    steps: Vec<TxPatternStep>,
+}
+
+/// The function calls of a receipt executed in the block
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ExecutedReceipt {
+    pub receipt_id: CryptoHash,
+    pub receiver_id: AccountId,
+    pub method_names: Vec<String>,
+}
+
+impl ExecutedReceipt {
+    pub(crate) fn from_outcome(outcome: &IndexerExecutionOutcomeWithReceipt) -> Self {
+        let method_names = match &outcome.receipt.receipt {
+            ReceiptEnumView::Action { actions, .. } => actions
+                .iter()
+                .filter_map(|action| match action {
+                    ActionView::FunctionCall { method_name, .. } => Some(method_name.clone()),
+                    _ => None,
+                })
+                .collect(),
+            ReceiptEnumView::Data { .. } => vec![],
+        };
+        Self {
+            receipt_id: outcome.receipt.receipt_id,
+            receiver_id: outcome.receipt.receiver_id.clone(),
+            method_names,
+        }
+    }
+}
+
+impl TxPattern {
+    /// Parses the pattern and makes sure it's about one of the watched accounts,
+    /// otherwise it would match the calls we are not interested in
+    pub(crate) fn parse(pattern: &str, watching_list: &[AccountId]) -> anyhow::Result<Self> {
+        let steps: Vec<TxPatternStep> = serde_json::from_str(pattern)
+            .map_err(|err| anyhow::anyhow!("Invalid --tx-pattern: {}", err))?;
+        if steps.is_empty() {
+            anyhow::bail!("--tx-pattern must have at least one step");
+        }
+        if !steps
+            .iter()
+            .any(|step| watching_list.contains(&step.receiver))
+        {
+            anyhow::bail!(
+                "--tx-pattern must have a step with one of the watched accounts as the receiver"
+            );
+        }
+        Ok(Self { steps })
+    }
+
+    /// Whether the receipt is sent to any of the receivers of the pattern
+    pub(crate) fn is_involved(&self, receipt: &ExecutedReceipt) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.receiver == receipt.receiver_id)
+    }
+
+    /// Checks all the steps are met by the receipts in the same order,
+    /// other receipts may come in between
+    pub(crate) fn matches(&self, receipts: &[ExecutedReceipt]) -> bool {
+        let mut receipts = receipts.iter();
+        self.steps.iter().all(|step| {
+            receipts.any(|receipt| {
+                receipt.receiver_id == step.receiver && receipt.method_names.contains(&step.method)
+            })
+        })
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use near_lake_framework::near_indexer_primitives::types::{AccountId, Balance};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+use crate::rpc::JsonRpcClient;
+
+/// Active validator of an epoch
+#[derive(Debug, Clone)]
+pub(crate) struct ValidatorInfo {
+    pub account_id: AccountId,
+    pub stake: Balance,
+}
+
+/// Difference between the validator sets of two consecutive epochs
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ValidatorSetEvent {
+    /// `validator_added`, `validator_removed` or `validator_stake_changed`
+    #[serde(skip)]
+    pub event_type: &'static str,
+    pub account_id: AccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_stake: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_stake: Option<String>,
+}
+
+pub(crate) struct ValidatorSetDiff;
+
+impl ValidatorSetDiff {
+    /// Returns the events sorted by the account, so the output is stable
+    pub(crate) fn compute(old: &[ValidatorInfo], new: &[ValidatorInfo]) -> Vec<ValidatorSetEvent> {
+        let old_stakes: HashMap<&AccountId, Balance> = old
+            .iter()
+            .map(|validator| (&validator.account_id, validator.stake))
+            .collect();
+        let new_stakes: HashMap<&AccountId, Balance> = new
+            .iter()
+            .map(|validator| (&validator.account_id, validator.stake))
+            .collect();
+        let mut events: Vec<ValidatorSetEvent> = new
+            .iter()
+            .filter_map(|validator| match old_stakes.get(&validator.account_id) {
+                None => Some(ValidatorSetEvent {
+                    event_type: "validator_added",
+                    account_id: validator.account_id.clone(),
+                    old_stake: None,
+                    new_stake: Some(validator.stake.to_string()),
+                }),
+                Some(old_stake) if *old_stake != validator.stake => Some(ValidatorSetEvent {
+                    event_type: "validator_stake_changed",
+                    account_id: validator.account_id.clone(),
+                    old_stake: Some(old_stake.to_string()),
+                    new_stake: Some(validator.stake.to_string()),
+                }),
+                Some(_) => None,
+            })
+            .collect();
+        events.extend(
+            old.iter()
+                .filter(|validator| !new_stakes.contains_key(&validator.account_id))
+                .map(|validator| ValidatorSetEvent {
+                    event_type: "validator_removed",
+                    account_id: validator.account_id.clone(),
+                    old_stake: Some(validator.stake.to_string()),
+                    new_stake: None,
+                }),
+        );
+        events.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        events
+    }
+}
+
+/// Fetches the active validators of the epoch from the RPC
+pub(crate) async fn fetch_validators(
+    rpc: &JsonRpcClient,
+    epoch_id: &CryptoHash,
+) -> anyhow::Result<Vec<ValidatorInfo>> {
+    let validators = rpc
+        .call(
+            "validators",
+            serde_json::json!({ "epoch_id": epoch_id.to_string() }),
+        )
+        .await?;
+    validators["current_validators"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("validators response has no current_validators"))?
+        .iter()
+        .map(|validator| {
+            let account_id = validator["account_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Validator has no account_id"))?
+                .parse()?;
+            let stake = validator["stake"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Validator {} has no stake", account_id))?
+                .parse()?;
+            Ok(ValidatorInfo { account_id, stake })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validator(account_id: &str, stake: Balance) -> ValidatorInfo {
+        ValidatorInfo {
+            account_id: account_id.parse().unwrap(),
+            stake,
+        }
+    }
+
+    fn summary(events: &[ValidatorSetEvent]) -> Vec<(&str, &str)> {
+        events
+            .iter()
+            .map(|event| (event.event_type, event.account_id.as_ref()))
+            .collect()
+    }
+
+    #[test]
+    fn added_validator() {
+        let events = ValidatorSetDiff::compute(
+            &[validator("a.poolv1.near", 10)],
+            &[
+                validator("a.poolv1.near", 10),
+                validator("b.poolv1.near", 20),
+            ],
+        );
+        assert_eq!(summary(&events), [("validator_added", "b.poolv1.near")]);
+        assert_eq!(events[0].old_stake, None);
+        assert_eq!(events[0].new_stake.as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn removed_validator() {
+        let events = ValidatorSetDiff::compute(
+            &[
+                validator("a.poolv1.near", 10),
+                validator("b.poolv1.near", 20),
+            ],
+            &[validator("a.poolv1.near", 10)],
+        );
+        assert_eq!(summary(&events), [("validator_removed", "b.poolv1.near")]);
+        assert_eq!(events[0].old_stake.as_deref(), Some("20"));
+        assert_eq!(events[0].new_stake, None);
+    }
+
+    #[test]
+    fn stake_changed_validator() {
+        let events = ValidatorSetDiff::compute(
+            &[validator("a.poolv1.near", 10)],
+            &[validator("a.poolv1.near", 15)],
+        );
+        assert_eq!(
+            summary(&events),
+            [("validator_stake_changed", "a.poolv1.near")]
+        );
+        assert_eq!(events[0].old_stake.as_deref(), Some("10"));
+        assert_eq!(events[0].new_stake.as_deref(), Some("15"));
+    }
+
+    #[test]
+    fn unchanged_validators_have_no_events() {
+        let validators = [
+            validator("a.poolv1.near", 10),
+            validator("b.poolv1.near", 20),
+        ];
+        assert!(ValidatorSetDiff::compute(&validators, &validators).is_empty());
+    }
+
+    #[test]
+    fn empty_previous_set_adds_everyone() {
+        let events = ValidatorSetDiff::compute(
+            &[],
+            &[
+                validator("b.poolv1.near", 20),
+                validator("a.poolv1.near", 10),
+            ],
+        );
+        assert_eq!(
+            summary(&events),
+            [
+                ("validator_added", "a.poolv1.near"),
+                ("validator_added", "b.poolv1.near"),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_are_sorted_by_account() {
+        let events = ValidatorSetDiff::compute(
+            &[
+                validator("d.poolv1.near", 40),
+                validator("b.poolv1.near", 20),
+                validator("c.poolv1.near", 30),
+            ],
+            &[
+                validator("c.poolv1.near", 35),
+                validator("a.poolv1.near", 10),
+                validator("b.poolv1.near", 20),
+            ],
+        );
+        assert_eq!(
+            summary(&events),
+            [
+                ("validator_added", "a.poolv1.near"),
+                ("validator_stake_changed", "c.poolv1.near"),
+                ("validator_removed", "d.poolv1.near"),
+            ]
+        );
+    }
+}
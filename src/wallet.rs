@@ -0,0 +1,78 @@
+use near_lake_framework::near_indexer_primitives::types::AccountId;
+use near_lake_framework::near_indexer_primitives::views::{
+    ActionView, ExecutionOutcomeView, ReceiptEnumView, StateChangeCauseView,
+    StateChangeWithCauseView,
+};
+use near_lake_framework::near_indexer_primitives::CryptoHash;
+
+use crate::block_index::BlockIndex;
+
+/// A transaction history item similar to the ones `near-api-js` works with
+/// in NEAR Wallet. We reconstruct it from the cause of the StateChange,
+/// so some of the fields might be missing (see README for the limitations)
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct WalletTransaction {
+    pub hash: Option<CryptoHash>,
+    pub receipt_id: Option<CryptoHash>,
+    pub signer_id: Option<AccountId>,
+    pub receiver_id: Option<AccountId>,
+    pub actions: Vec<ActionView>,
+    pub block_hash: CryptoHash,
+    pub block_timestamp: u64,
+    pub outcome: Option<ExecutionOutcomeView>,
+}
+
+impl WalletTransaction {
+    pub(crate) fn from_state_change(
+        state_change: &StateChangeWithCauseView,
+        block_index: &BlockIndex,
+        block_hash: CryptoHash,
+        block_timestamp: u64,
+    ) -> Self {
+        let mut wallet_transaction = Self {
+            hash: None,
+            receipt_id: None,
+            signer_id: None,
+            receiver_id: None,
+            actions: vec![],
+            block_hash,
+            block_timestamp,
+            outcome: None,
+        };
+
+        match &state_change.cause {
+            StateChangeCauseView::TransactionProcessing { tx_hash } => {
+                wallet_transaction.hash = Some(*tx_hash);
+                if let Some(transaction) = block_index.transactions.get(tx_hash) {
+                    wallet_transaction.signer_id = Some(transaction.transaction.signer_id.clone());
+                    wallet_transaction.receiver_id =
+                        Some(transaction.transaction.receiver_id.clone());
+                    wallet_transaction.actions = transaction.transaction.actions.clone();
+                    wallet_transaction.outcome =
+                        Some(transaction.outcome.execution_outcome.outcome.clone());
+                }
+            }
+            StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+            | StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+            | StateChangeCauseView::ReceiptProcessing { receipt_hash }
+            | StateChangeCauseView::PostponedReceipt { receipt_hash } => {
+                wallet_transaction.receipt_id = Some(*receipt_hash);
+                if let Some(outcome) = block_index.receipts.get(receipt_hash) {
+                    wallet_transaction.receiver_id = Some(outcome.receipt.receiver_id.clone());
+                    if let ReceiptEnumView::Action {
+                        signer_id, actions, ..
+                    } = &outcome.receipt.receipt
+                    {
+                        wallet_transaction.signer_id = Some(signer_id.clone());
+                        wallet_transaction.actions = actions.clone();
+                    }
+                    wallet_transaction.outcome = Some(outcome.execution_outcome.outcome.clone());
+                }
+            }
+            // Other causes are not related to any transaction
+            _ => {}
+        }
+
+        wallet_transaction
+    }
+}
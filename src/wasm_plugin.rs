@@ -0,0 +1,143 @@
+//! User-defined filtering and transformation of the matched changes in a
+//! WebAssembly module. Needs the `wasm-plugin` cargo feature.
+//!
+//! The module has no imports and exports:
+//! - `memory`
+//! - `guest_alloc(len: i32) -> i32` and `guest_dealloc(ptr: i32, len: i32)`
+//!   to let us pass the event JSON into the memory
+//! - `guest_filter(event_ptr: i32, event_len: i32) -> i32` (optional),
+//!   returns 0 to drop the event and 1 to keep it
+//! - `guest_transform(event_ptr: i32, event_len: i32, out_ptr: i32, out_len: i32) -> i32`
+//!   (optional), writes the new event JSON to the output buffer and returns its length.
+//!   If the returned length is bigger than `out_len` we call it again with a bigger buffer,
+//!   a negative length is an error
+//!
+//! See `wasm-plugin-template` for a plugin written in Rust.
+
+use std::path::Path;
+
+#[cfg(feature = "wasm-plugin")]
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// Runs the plugin with its own fuel on every event, so a plugin stuck in a loop
+/// fails the event instead of hanging the watcher
+pub(crate) struct WasmPlugin {
+    #[cfg(feature = "wasm-plugin")]
+    store: Store<()>,
+    #[cfg(feature = "wasm-plugin")]
+    memory: Memory,
+    #[cfg(feature = "wasm-plugin")]
+    alloc: TypedFunc<i32, i32>,
+    #[cfg(feature = "wasm-plugin")]
+    dealloc: TypedFunc<(i32, i32), ()>,
+    #[cfg(feature = "wasm-plugin")]
+    filter: Option<TypedFunc<(i32, i32), i32>>,
+    #[cfg(feature = "wasm-plugin")]
+    transform: Option<TypedFunc<(i32, i32, i32, i32), i32>>,
+    #[cfg(feature = "wasm-plugin")]
+    fuel_limit: u64,
+}
+
+#[cfg(feature = "wasm-plugin")]
+impl WasmPlugin {
+    pub(crate) fn load(path: &Path, fuel_limit: u64) -> anyhow::Result<Self> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)
+            .map_err(|err| anyhow::anyhow!("Failed to load {}: {}", path.display(), err))?;
+        let mut store = Store::new(&engine, ());
+        store.set_fuel(fuel_limit)?;
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow::anyhow!("{} doesn't export memory", path.display()))?;
+        Ok(Self {
+            memory,
+            alloc: instance.get_typed_func(&mut store, "guest_alloc")?,
+            dealloc: instance.get_typed_func(&mut store, "guest_dealloc")?,
+            filter: instance.get_typed_func(&mut store, "guest_filter").ok(),
+            transform: instance.get_typed_func(&mut store, "guest_transform").ok(),
+            store,
+            fuel_limit,
+        })
+    }
+
+    /// Returns the transformed event or `None` if the plugin drops it
+    pub(crate) fn apply(
+        &mut self,
+        event: &serde_json::Value,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        self.store.set_fuel(self.fuel_limit)?;
+        let event = event.to_string();
+        let event_len = event.len() as i32;
+        let event_ptr = self.alloc.call(&mut self.store, event_len)?;
+        self.memory
+            .write(&mut self.store, event_ptr as usize, event.as_bytes())?;
+
+        let result = self.call(event_ptr, event_len);
+        self.dealloc.call(&mut self.store, (event_ptr, event_len))?;
+        match result? {
+            Some(transformed) => Ok(Some(serde_json::from_slice(&transformed)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn call(&mut self, event_ptr: i32, event_len: i32) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(filter) = &self.filter {
+            if filter.call(&mut self.store, (event_ptr, event_len))? == 0 {
+                return Ok(None);
+            }
+        }
+        let transform = match &self.transform {
+            Some(transform) => transform,
+            None => {
+                let mut event = vec![0; event_len as usize];
+                self.memory
+                    .read(&self.store, event_ptr as usize, &mut event)?;
+                return Ok(Some(event));
+            }
+        };
+
+        // Most transformations add a few fields, so twice the event is usually enough
+        let mut out_len = event_len.saturating_mul(2).max(1024);
+        loop {
+            let out_ptr = self.alloc.call(&mut self.store, out_len)?;
+            let written = transform.call(&mut self.store, (event_ptr, event_len, out_ptr, out_len));
+            let mut transformed = vec![];
+            if let Ok(written) = written {
+                if (0..=out_len).contains(&written) {
+                    transformed.resize(written as usize, 0);
+                    self.memory
+                        .read(&self.store, out_ptr as usize, &mut transformed)?;
+                }
+            }
+            self.dealloc.call(&mut self.store, (out_ptr, out_len))?;
+
+            let written = written?;
+            if written < 0 {
+                anyhow::bail!("guest_transform failed with {}", written);
+            }
+            if written <= out_len {
+                return Ok(Some(transformed));
+            }
+            out_len = written;
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm-plugin"))]
+impl WasmPlugin {
+    pub(crate) fn load(_path: &Path, _fuel_limit: u64) -> anyhow::Result<Self> {
+        anyhow::bail!(
+            "The watcher was built without WASM plugins, rebuild it with `--features wasm-plugin`"
+        )
+    }
+
+    pub(crate) fn apply(
+        &mut self,
+        _event: &serde_json::Value,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        unreachable!("WasmPlugin can't be loaded without the wasm-plugin feature")
+    }
+}
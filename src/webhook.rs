@@ -0,0 +1,46 @@
+/// POSTs the alerts as JSON to the `--alert-webhook` URL
+pub(crate) struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Webhook {
+    pub(crate) fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    /// Sends the alert in the background, so a slow endpoint doesn't hold
+    /// the blocks back. The failures are only reported to stderr
+    pub(crate) fn send(&self, alert: &impl serde::Serialize) {
+        post_in_background(self.client.post(&self.url).json(alert), "the webhook");
+    }
+
+    /// Sends a test event and waits for the endpoint to accept it
+    pub(crate) async fn ping(&self) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(&serde_json::json!({ "event": "ping" }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sends the request without waiting for the response,
+/// `destination` names the receiver in the error message
+pub(crate) fn post_in_background(request: reqwest::RequestBuilder, destination: &'static str) {
+    tokio::spawn(async move {
+        match request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => {}
+            Err(err) => eprintln!("Failed to send the alert to {}: {}", destination, err),
+        }
+    });
+}
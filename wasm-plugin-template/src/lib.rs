@@ -0,0 +1,73 @@
+//! Example plugin for `--wasm-plugin`. It keeps only the `account_update`
+//! changes and adds the account balance in NEAR to them.
+//!
+//! Build it with
+//! ```bash
+//! $ rustup target add wasm32-unknown-unknown
+//! $ cargo build --release --target wasm32-unknown-unknown
+//! ```
+//! and pass `target/wasm32-unknown-unknown/release/wasm_plugin_template.wasm` to the watcher.
+
+use std::slice;
+
+/// Gives the watcher a buffer in our memory to pass the event in
+/// (or to receive the transformed event)
+#[no_mangle]
+pub extern "C" fn guest_alloc(len: i32) -> i32 {
+    let mut buffer = Vec::<u8>::with_capacity(len as usize);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr as i32
+}
+
+/// Frees the buffer created by `guest_alloc`
+#[no_mangle]
+pub extern "C" fn guest_dealloc(ptr: i32, len: i32) {
+    unsafe { drop(Vec::from_raw_parts(ptr as *mut u8, 0, len as usize)) }
+}
+
+/// Returns 1 to keep the event and 0 to drop it
+#[no_mangle]
+pub extern "C" fn guest_filter(event_ptr: i32, event_len: i32) -> i32 {
+    let event = match read_event(event_ptr, event_len) {
+        Some(event) => event,
+        None => return 0,
+    };
+    (event["type"] == "account_update") as i32
+}
+
+/// Writes the transformed event to the output buffer and returns its length.
+/// If the event doesn't fit we return the length we need and get called again
+/// with a bigger buffer, a negative length means we have failed
+#[no_mangle]
+pub extern "C" fn guest_transform(
+    event_ptr: i32,
+    event_len: i32,
+    out_ptr: i32,
+    out_len: i32,
+) -> i32 {
+    let mut event = match read_event(event_ptr, event_len) {
+        Some(event) => event,
+        None => return -1,
+    };
+    if let Some(amount) = event["change"]["amount"]
+        .as_str()
+        .and_then(|amount| amount.parse::<u128>().ok())
+    {
+        // Good enough for display, the exact amount is still in `change`
+        event["amount_near"] = (amount as f64 / 1e24).into();
+    }
+
+    let transformed = event.to_string();
+    if transformed.len() > out_len as usize {
+        return transformed.len() as i32;
+    }
+    let out = unsafe { slice::from_raw_parts_mut(out_ptr as *mut u8, transformed.len()) };
+    out.copy_from_slice(transformed.as_bytes());
+    transformed.len() as i32
+}
+
+fn read_event(event_ptr: i32, event_len: i32) -> Option<serde_json::Value> {
+    let event = unsafe { slice::from_raw_parts(event_ptr as *const u8, event_len as usize) };
+    serde_json::from_slice(event).ok()
+}